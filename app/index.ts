@@ -1,25 +1,69 @@
 import { Nes } from '../pkg';
 import './index.scss';
 
-const NES_FPS = 60;
+const SRAM_SAVE_INTERVAL = 5000;
 
 class App {
 	nes: Nes;
 	ticker: number;
 	nextFrame: number;
+	sramTicker: number;
+	romName: string | null;
 
 	constructor(nes: Nes) {
 		this.nes = nes;
 		this.ticker = -1;
 		this.nextFrame = -1;
+		this.sramTicker = -1;
+		this.romName = null;
 
 		this.render = this.render.bind(this);
 		this.tickFrame = this.tickFrame.bind(this);
+		this.saveSram = this.saveSram.bind(this);
 	}
 
 	start(): void {
-		this.ticker = window.setInterval(this.tickFrame, 1000 / NES_FPS);
+		this.startTicker();
 		this.nextFrame = window.requestAnimationFrame(this.render);
+		this.sramTicker = window.setInterval(this.saveSram, SRAM_SAVE_INTERVAL);
+
+		window.addEventListener('beforeunload', this.saveSram);
+	}
+
+	// pal and dendy roms run at 50hz
+	startTicker(): void {
+		window.clearInterval(this.ticker);
+		this.ticker = window.setInterval(this.tickFrame, 1000 / this.nes.frame_rate());
+	}
+
+	loadRom(name: string, rom: Uint8Array): void {
+		this.saveSram();
+		this.nes.load_rom(rom);
+		this.romName = name;
+		this.startTicker();
+
+		const saved = window.localStorage.getItem(this.sramKey(name));
+
+		if (saved !== null && this.nes.has_battery()) {
+			const sram = Uint8Array.from(window.atob(saved), c => c.charCodeAt(0));
+			this.nes.load_sram(sram);
+		}
+	}
+
+	sramKey(name: string): string {
+		return `sram:${name}`;
+	}
+
+	saveSram(): void {
+		if (this.romName === null || !this.nes.sram_dirty()) {
+			return;
+		}
+
+		const sram = this.nes.sram();
+		const encoded = window.btoa(String.fromCharCode(...sram));
+
+		window.localStorage.setItem(this.sramKey(this.romName), encoded);
+		this.nes.mark_sram_saved();
 	}
 
 	render(): void {
@@ -0,0 +1,210 @@
+// compares the page table decoder against the bus interface it replaced,
+// and lock step against catch up ppu timing,
+// run with `cargo bench --bench bus`
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use nes::Nes;
+use nes::machine::PpuSync;
+use nes::bus::{Bus, BusRead, BusWrite, CpuBus};
+use nes::memory::Memory;
+use nes::ppu::Ppu;
+use nes::input::Input;
+use nes::cartridge::Cartridge;
+
+const ACCESSES: usize = 2_000_000;
+// each case runs this many times interleaved with the others, keeping the
+// fastest so a noisy neighbour doesn't decide the result
+const ROUNDS: usize = 10;
+const FRAMES: u64 = 600;
+
+// the bus before the page table, as it was in src/bus.rs. every access
+// asks each device in the order they were added until one answers, then
+// tells the watchers
+trait BusWatch {
+    fn access(&mut self, addr: u16, value: u8, write: bool);
+}
+
+struct BusInterface {
+    readers: Vec<Rc<RefCell<dyn BusRead>>>,
+    writers: Vec<Rc<RefCell<dyn BusWrite>>>,
+    watchers: Vec<Rc<RefCell<dyn BusWatch>>>,
+}
+
+impl BusInterface {
+    fn read(&mut self, addr: usize) -> u8 {
+        let addr = addr & 0xffff;
+        let mut value = 0;
+
+        for reader in self.readers.iter() {
+            if let Some(byte) = reader.borrow_mut().read(addr) {
+                value = byte;
+                break;
+            }
+        }
+
+        for watcher in self.watchers.iter() {
+            watcher.borrow_mut().access(addr as u16, value, false);
+        }
+
+        value
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        let addr = addr & 0xffff;
+
+        for writer in self.writers.iter() {
+            if writer.borrow_mut().write(addr, value) {
+                break;
+            }
+        }
+
+        for watcher in self.watchers.iter() {
+            watcher.borrow_mut().access(addr as u16, value, true);
+        }
+    }
+}
+
+// the ppu used to hold the cartridge and decode its own registers, now
+// it's handed the cartridge on every access
+struct PpuRegisters {
+    ppu: Ppu,
+    cartridge: Rc<RefCell<Cartridge>>,
+}
+
+impl BusRead for PpuRegisters {
+    fn read(&mut self, addr: usize) -> Option<u8> {
+        match addr {
+            0x2000..=0x3fff => Some(self.ppu.read(Some(&self.cartridge.borrow()), addr)),
+            _ => None,
+        }
+    }
+
+    fn peek(&self, addr: usize) -> Option<u8> {
+        match addr {
+            0x2000..=0x3fff => Some(self.ppu.peek(Some(&self.cartridge.borrow()), addr)),
+            _ => None,
+        }
+    }
+}
+
+impl BusWrite for PpuRegisters {
+    fn write(&mut self, addr: usize, value: u8) -> bool {
+        match addr {
+            0x2000..=0x3fff => {
+                self.ppu.write(Some(&mut self.cartridge.borrow_mut()), addr, value);
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+// wired up the way the old `Bus::new` and `Bus::insert` did it
+fn bus_interface(cartridge: Cartridge) -> BusInterface {
+    let ram = Rc::new(RefCell::new(Memory::new()));
+    let input = Rc::new(RefCell::new(Input::new()));
+    let cartridge = Rc::new(RefCell::new(cartridge));
+    let ppu = Rc::new(RefCell::new(PpuRegisters { ppu: Ppu::new(), cartridge: cartridge.clone() }));
+
+    BusInterface {
+        readers: vec![ram.clone(), ppu.clone(), input.clone(), cartridge.clone()],
+        writers: vec![ram, ppu, input, cartridge],
+        watchers: vec![],
+    }
+}
+
+// a 16k nrom image spinning on CLC; BCC -2
+fn idle_rom() -> Vec<u8> {
+    let mut rom = vec![b'N', b'E', b'S', 0x1a, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0; 16 * 1024];
+
+    prg[..3].copy_from_slice(&[0x18, 0x90, 0xfd]);
+    prg[0x3ffc] = 0x00;
+    prg[0x3ffd] = 0xc0;
+
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 8 * 1024]);
+    rom
+}
+
+// roughly what a game does: mostly rom and ram, some prg ram, the odd
+// register. a power of two long so picking one is a mask
+fn addresses() -> Vec<usize> {
+    let pattern = [0xc000, 0x0010, 0xc001, 0x0200, 0xc002, 0x6000, 0xc003, 0x2002, 0xc004, 0x0300];
+
+    (0..4096).map(|i| pattern[i % pattern.len()] + (i & 0x3f)).collect()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let ns = elapsed.as_nanos() as f64 / ACCESSES as f64;
+    println!("{:<24} {:>8.2} ns/access", name, ns);
+}
+
+fn time<F: FnMut(usize)>(addresses: &[usize], mut access: F) -> Duration {
+    let start = Instant::now();
+
+    for i in 0..ACCESSES {
+        access(addresses[i & (addresses.len() - 1)]);
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let mut bus = Bus::new();
+    bus.insert(Cartridge::new(&idle_rom()).unwrap());
+
+    let mut interface = bus_interface(Cartridge::new(&idle_rom()).unwrap());
+    let addresses = addresses();
+
+    let mut best = [Duration::MAX; 4];
+
+    for _ in 0..ROUNDS {
+        let times = [
+            time(&addresses, |addr| { black_box(bus.read(black_box(addr))); }),
+            time(&addresses, |addr| { black_box(interface.read(black_box(addr))); }),
+            time(&addresses, |addr| bus.write(black_box(addr), 0)),
+            time(&addresses, |addr| interface.write(black_box(addr), 0)),
+        ];
+
+        for (best, time) in best.iter_mut().zip(times.iter()) {
+            *best = (*best).min(*time);
+        }
+    }
+
+    report("read, page table", best[0]);
+    report("read, bus interface", best[1]);
+    report("write, page table", best[2]);
+    report("write, bus interface", best[3]);
+
+    println!("read speedup  {:.2}x", best[1].as_secs_f64() / best[0].as_secs_f64());
+    println!("write speedup {:.2}x", best[3].as_secs_f64() / best[2].as_secs_f64());
+
+    let lock_step = time_frames(PpuSync::LockStep);
+    let catch_up = time_frames(PpuSync::CatchUp);
+
+    report_frames("frames, lock step", lock_step);
+    report_frames("frames, catch up", catch_up);
+
+    println!("catch up speedup {:.2}x", lock_step.as_secs_f64() / catch_up.as_secs_f64());
+}
+
+fn time_frames(mode: PpuSync) -> Duration {
+    let mut nes = Nes::new();
+    nes.load_rom(&idle_rom()).unwrap();
+    nes.set_ppu_sync(mode);
+
+    let start = Instant::now();
+
+    for _ in 0..FRAMES {
+        nes.tick_frame();
+    }
+
+    start.elapsed()
+}
+
+fn report_frames(name: &str, elapsed: Duration) {
+    println!("{:<24} {:>8.2} ms/frame", name, elapsed.as_secs_f64() * 1000.0 / FRAMES as f64);
+}
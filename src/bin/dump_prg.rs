@@ -0,0 +1,47 @@
+use std::env;
+use std::fs;
+use std::process;
+use nes::cartridge::Cartridge;
+use nes::disasm;
+
+// usage: dump_prg <rom.nes> [start] [end]
+//
+// prints a labelled listing of the rom's prg as it's mapped at $8000-$ffff,
+// addresses are hex without a prefix
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.is_empty() || args.len() > 3 {
+        eprintln!("usage: dump_prg <rom.nes> [start] [end]");
+        process::exit(2);
+    }
+
+    let rom = fs::read(&args[0]).unwrap_or_else(|error| {
+        eprintln!("{}: {}", args[0], error);
+        process::exit(1);
+    });
+
+    let cartridge = Cartridge::new(&rom).unwrap_or_else(|error| {
+        eprintln!("{}: {}", args[0], error);
+        process::exit(1);
+    });
+
+    let parse = |i: usize, default: u16| match args.get(i) {
+        Some(arg) => u16::from_str_radix(arg.trim_start_matches('$'), 16).unwrap_or_else(|_| {
+            eprintln!("bad address: {}", arg);
+            process::exit(2);
+        }),
+        None => default,
+    };
+
+    let start = parse(1, 0x8000).max(0x8000);
+    let end = parse(2, 0xffff);
+
+    // 16k roms are mirrored into both halves, same as on the bus
+    let prg = &cartridge.prg_rom;
+    let bytes: Vec<u8> = (start..=end)
+        .map(|addr| prg[(addr as usize - 0x8000) % prg.len()])
+        .collect();
+
+    print!("{}", disasm::to_text(&disasm::disassemble(&bytes, start)));
+}
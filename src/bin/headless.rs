@@ -0,0 +1,241 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use nes::Nes;
+use nes::audio::{self, DEFAULT_SAMPLE_RATE};
+use nes::headless::InputScript;
+use nes::inspect::MemorySpace;
+use nes::machine::PpuSync;
+use nes::region::Region;
+use nes::sram;
+use nes::trace::WriteSink;
+use nes::wav::SampleFormat;
+
+const USAGE: &str = "usage: headless <rom.nes> [options]
+
+  --frames N          frames to run, 60 by default
+  --region NAME       ntsc, pal or dendy, overriding the header
+  --input FILE        input script, lines of `<frame> <player> <buttons>`
+  --movie FILE        plays an fm2 movie instead
+  --screenshot FILE   saves the last frame as png
+  --screenshots DIR   saves every `--every` frames as png into DIR
+  --every N           frames between screenshots, 60 by default
+  --hash              prints the hash of the last frame
+  --hashes FILE       writes `<frame> <hash>` for every frame
+
+  --wav FILE          records the mixed audio
+  --stems DIR         records each apu channel on its own into DIR
+  --sample-rate N     audio sample rate, 44100 by default
+  --float             writes 32 bit float samples instead of 16 bit
+  --trace FILE        writes a nestest style trace of every instruction
+  --ram FILE          dumps the 2k of cpu ram after the last frame
+  --sav FILE          battery save loaded before and written back after the
+                      run, next to the rom as .sav by default
+
+screenshots and hashes are of the frame rebuilt from the ppu's state at
+its end, changes made mid frame don't show up in them";
+
+// runs a rom without a browser, for ci and servers
+fn main() {
+    let options = Options::parse(env::args().skip(1).collect()).unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+
+    if let Err(error) = run(&options) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    rom: PathBuf,
+    frames: u64,
+    region: Option<Region>,
+    input: Option<PathBuf>,
+    movie: Option<PathBuf>,
+    screenshot: Option<PathBuf>,
+    screenshots: Option<PathBuf>,
+    every: u64,
+    hash: bool,
+    hashes: Option<PathBuf>,
+    wav: Option<PathBuf>,
+    stems: Option<PathBuf>,
+    sample_rate: u32,
+    format: SampleFormat,
+    trace: Option<PathBuf>,
+    ram: Option<PathBuf>,
+    sav: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(args: Vec<String>) -> Result<Options, String> {
+        let mut options = Options {
+            frames: 60,
+            every: 60,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            ..Options::default()
+        };
+        let mut rom = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                if rom.replace(PathBuf::from(&arg)).is_some() {
+                    return Err(format!("unexpected argument `{}`", arg));
+                }
+
+                continue;
+            }
+
+            // the options without a value
+            match arg.as_str() {
+                "--hash" => {
+                    options.hash = true;
+                    continue;
+                },
+                "--float" => {
+                    options.format = SampleFormat::Float32;
+                    continue;
+                },
+                _ => {},
+            }
+
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            let number = || value.parse::<u64>().map_err(|_| format!("{}: bad number `{}`", arg, value));
+
+            match arg.as_str() {
+                "--frames" => options.frames = number()?,
+                "--every" => options.every = number()?.max(1),
+                "--region" => options.region = Some(match value.as_str() {
+                    "ntsc" => Region::Ntsc,
+                    "pal" => Region::Pal,
+                    "dendy" => Region::Dendy,
+                    _ => return Err(format!("unknown region `{}`", value)),
+                }),
+                "--input" => options.input = Some(value.into()),
+                "--movie" => options.movie = Some(value.into()),
+                "--screenshot" => options.screenshot = Some(value.into()),
+                "--screenshots" => options.screenshots = Some(value.into()),
+                "--hashes" => options.hashes = Some(value.into()),
+                "--wav" => options.wav = Some(value.into()),
+                "--stems" => options.stems = Some(value.into()),
+                "--sample-rate" => options.sample_rate = number()?.clamp(1, u32::MAX as u64) as u32,
+                "--trace" => options.trace = Some(value.into()),
+                "--ram" => options.ram = Some(value.into()),
+                "--sav" => options.sav = Some(value.into()),
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+
+        if options.input.is_some() && options.movie.is_some() {
+            return Err("--input and --movie can't be used together".to_string());
+        }
+
+        options.rom = rom.ok_or("no rom given")?;
+        Ok(options)
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let read = |path: &PathBuf| fs::read(path).map_err(|error| format!("{}: {}", path.display(), error));
+    let write = |path: &PathBuf, bytes: &[u8]| fs::write(path, bytes).map_err(|error| format!("{}: {}", path.display(), error));
+
+    let mut nes = Nes::new();
+
+    // same results as lock step, only faster
+    nes.set_ppu_sync(PpuSync::CatchUp);
+    nes.load_rom(&read(&options.rom)?).map_err(|error| format!("{}: {}", options.rom.display(), error))?;
+
+    if let Some(region) = options.region {
+        nes.set_region(region);
+    }
+
+    // only battery backed cartridges read or write it
+    let sav = options.sav.clone().unwrap_or_else(|| sram::path_for_rom(&options.rom));
+    nes.load_sram_file(&sav).map_err(|error| format!("{}: {}", sav.display(), error))?;
+
+    let script = match &options.input {
+        Some(path) => {
+            let text = String::from_utf8_lossy(&read(path)?).into_owned();
+            InputScript::parse(&text).map_err(|error| format!("{}: {}", path.display(), error))?
+        },
+        None => InputScript::new(),
+    };
+
+    if let Some(path) = &options.movie {
+        let text = String::from_utf8_lossy(&read(path)?).into_owned();
+        nes.play_fm2(&text).map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+
+    if let Some(path) = &options.trace {
+        let file = File::create(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        nes.set_trace_sink(Box::new(WriteSink::new(BufWriter::new(file))));
+    }
+
+    for dir in options.screenshots.iter().chain(&options.stems) {
+        fs::create_dir_all(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    }
+
+    if options.wav.is_some() || options.stems.is_some() {
+        nes.start_audio_capture(options.sample_rate, options.stems.is_some());
+    }
+
+    let mut hashes = match &options.hashes {
+        Some(path) => Some(BufWriter::new(File::create(path).map_err(|error| format!("{}: {}", path.display(), error))?)),
+        None => None,
+    };
+
+    for frame in 0..options.frames {
+        script.apply(&mut nes, frame);
+        nes.tick_frame();
+
+        if let (Some(file), Some(path)) = (&mut hashes, &options.hashes) {
+            writeln!(file, "{} {:016x}", frame + 1, nes.reconstructed_frame_hash()).map_err(|error| format!("{}: {}", path.display(), error))?;
+        }
+
+        if let Some(dir) = &options.screenshots {
+            if (frame + 1) % options.every == 0 {
+                write(&dir.join(format!("frame{:06}.png", frame + 1)), &nes.screenshot())?;
+            }
+        }
+    }
+
+    if let Some(path) = &options.screenshot {
+        write(path, &nes.screenshot())?;
+    }
+
+    if let (Some(file), Some(path)) = (&mut hashes, &options.hashes) {
+        file.flush().map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+
+    if options.hash {
+        println!("{:016x}", nes.reconstructed_frame_hash());
+    }
+
+    if let Some(path) = &options.wav {
+        write(path, &nes.audio_wav(options.format))?;
+    }
+
+    if let Some(dir) = &options.stems {
+        for (channel, name) in audio::CHANNELS.iter().enumerate() {
+            let wav = nes.audio_stem_wav(channel, options.format).unwrap_or_default();
+            write(&dir.join(format!("{}.wav", name)), &wav)?;
+        }
+    }
+
+    if let Some(path) = &options.ram {
+        write(path, &nes.peek_range(MemorySpace::Ram, 0, nes.memory_size(MemorySpace::Ram)))?;
+    }
+
+    nes.save_sram_file(&sav).map_err(|error| format!("{}: {}", sav.display(), error))?;
+
+    if let Some(path) = &options.trace {
+        nes.flush_trace().map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+
+    Ok(())
+}
@@ -1,89 +1,416 @@
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::ops::RangeInclusive;
 use super::{
-    cpu::Cpu,
+    Tick,
+    cpu::OAM_DMA,
     ppu::Ppu,
-    memory::Memory,
+    frame_counter::{FrameCounter, FRAME_COUNTER},
+    memory::{self, Memory},
+    input::Input,
+    cartridge::Cartridge,
+    state::{Snapshot, StateReader, StateWriter, StateError},
 };
 
+// `read` is what the cpu does and may change the device (clearing a flag,
+// shifting a controller), `peek` returns the same value without doing so
+// and is what debuggers and other tools looking at memory use
 pub trait BusRead {
-    fn read(&self, addr: usize) -> Option<u8>;
+    fn read(&mut self, addr: usize) -> Option<u8>;
+    fn peek(&self, addr: usize) -> Option<u8>;
 }
 
 pub trait BusWrite {
     fn write(&mut self, addr: usize, value: u8) -> bool;
 }
 
-pub struct BusInterface {
-    pub readers: Vec<Rc<RefCell<dyn BusRead>>>,
-    pub writers: Vec<Rc<RefCell<dyn BusWrite>>>,
+// something plugged into the bus on top of the console's own devices, an
+// expansion port peripheral or a test fixture. it's cloned with the
+// machine, so it has to be owned
+pub trait BusDevice: BusRead + BusWrite {
+    fn box_clone(&self) -> Box<dyn BusDevice + Send>;
 }
 
-impl BusInterface {
-    pub fn new() -> Rc<RefCell<BusInterface>> {
-        Rc::new(RefCell::new(BusInterface {
-            readers: vec![],
-            writers: vec![],
-        }))
+impl<T: BusRead + BusWrite + Clone + Send + 'static> BusDevice for T {
+    fn box_clone(&self) -> Box<dyn BusDevice + Send> {
+        Box::new(self.clone())
     }
+}
 
-    pub fn read(&self, addr: usize) -> u8 {
-        let addr = addr & 0xffff;
+impl Clone for Box<dyn BusDevice + Send> {
+    fn clone(&self) -> Box<dyn BusDevice + Send> {
+        self.box_clone()
+    }
+}
 
-        for reader in self.readers.iter() {
-            if let Some(value) = reader.borrow().read(addr) {
-                return value;
-            }
+// the 64k the cpu is wired to, handed to it for every tick
+pub trait CpuBus {
+    fn read(&mut self, addr: usize) -> u8;
+    fn write(&mut self, addr: usize, value: u8);
+    fn peek(&self, addr: usize) -> u8;
+}
+
+// one access made through the bus, as seen by watchpoints
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Access {
+    pub addr: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+// the cpu's 64k is decoded in 256 byte pages, each page only asks the
+// devices mapped into it
+pub const PAGE_SIZE: usize = 0x100;
+pub const PAGES: usize = 0x10000 / PAGE_SIZE;
+
+pub const APU_STATUS: usize = 0x4015;
+
+// devices that can hold the irq line, the cpu sees it while any of them do
+pub const IRQ_FRAME_COUNTER: u8 = 0b00000001;
+pub const IRQ_CARTRIDGE: u8 = 0b00000010;
+
+// bits of the io registers nothing drives, they read back whatever was
+// last on the data bus
+fn undriven(addr: usize) -> u8 {
+    match addr {
+        APU_STATUS => 0x20,
+        0x4016 | 0x4017 => 0xe0,
+        _ => 0,
+    }
+}
+
+// what a page decodes to. ram and prg rom are read often enough to index
+// their memory directly instead of asking the device
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Page {
+    Open,
+    Ram,
+    Ppu,
+    // apu and controllers, with the cartridge taking over from $4020
+    Io,
+    Cartridge,
+    // the page's offset into prg rom is worked out once, on insert
+    PrgRom(usize),
+    // an index into `devices`
+    Device(usize),
+}
+
+#[derive(Clone)]
+pub struct Bus {
+    pub ppu: Ppu,
+    pub ram: Memory,
+    pub input: Input,
+    pub cartridge: Option<Cartridge>,
+    pub frame_counter: FrameCounter,
+
+    // the `IRQ_*` sources currently asserting the line
+    pub irq: u8,
+    // set by a write to $4014, for the machine to schedule
+    pub dma: Option<u8>,
+
+    // master clock the ppu's next dot starts on
+    pub ppu_clock: u64,
+    // when set the ppu is left behind and only brought up to `now`, the
+    // clock of the access being made, when the cpu could notice. `synced`
+    // tells the machine it happened
+    pub catch_up: bool,
+    pub now: u64,
+    pub synced: bool,
+
+    // every access is collected here while it's `Some`, the debugger
+    // turns it on for watchpoints
+    pub log: Option<Vec<Access>>,
+
+    // the last value on the data bus, which is what reads nothing answers
+    // return. it's the byte just read or written, usually the high byte
+    // of the address for an unmapped absolute read
+    pub open_bus: u8,
+
+    pages: [Page; PAGES],
+    // mapped with `map`, each over the pages its range touches
+    devices: Vec<(RangeInclusive<usize>, Box<dyn BusDevice + Send>)>,
+}
+
+impl Bus {
+    pub fn new() -> Bus {
+        let mut bus = Bus {
+            ppu: Ppu::new(),
+            ram: Memory::new(),
+            input: Input::new(),
+            cartridge: None,
+            frame_counter: FrameCounter::new(),
+            irq: 0,
+            dma: None,
+            ppu_clock: 0,
+            catch_up: false,
+            now: 0,
+            synced: false,
+            log: None,
+            open_bus: 0,
+            pages: [Page::Open; PAGES],
+            devices: vec![],
+        };
+
+        bus.decode();
+        bus
+    }
+
+    // fresh ppu, ram, controllers and frame counter, with no irq or dma
+    // pending, the clocks at 0 and nothing left on the data bus. the
+    // cartridge, mapped devices, sync mode and access log stay as they are
+    pub fn power_on(&mut self) {
+        let mode = self.input.mode;
+        let region = self.ppu.region;
+
+        self.ppu = Ppu::new();
+        self.ppu.region = region;
+        self.ram = Memory::new();
+        self.input = Input::new();
+        self.input.mode = mode;
+        self.frame_counter = FrameCounter::new();
+        self.irq = 0;
+        self.dma = None;
+        self.ppu_clock = 0;
+        self.now = 0;
+        self.open_bus = 0;
+    }
+
+    pub fn insert(&mut self, cartridge: Cartridge) {
+        self.cartridge = Some(cartridge);
+        self.decode();
+    }
+
+    pub fn eject(&mut self) {
+        self.cartridge = None;
+        self.decode();
+    }
+
+    // takes over every page `range` touches from whatever was there,
+    // including a device mapped earlier. addresses the device turns down
+    // read as open bus. devices aren't part of save states
+    pub fn map(&mut self, range: RangeInclusive<usize>, device: Box<dyn BusDevice + Send>) -> usize {
+        self.devices.push((range, device));
+        self.decode();
+        self.devices.len() - 1
+    }
+
+    pub fn device(&self, index: usize) -> Option<&(dyn BusDevice + Send)> {
+        self.devices.get(index).map(|(_, device)| device.as_ref())
+    }
+
+    // what the ppu sees at `addr` of its own address space
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        self.ppu.read_vram(self.cartridge.as_ref(), addr)
+    }
+
+    // runs the ppu dots that start before `end`
+    pub fn run_ppu(&mut self, end: u64) {
+        let divider = self.ppu.region.ppu_divider();
+
+        while self.ppu_clock < end {
+            self.ppu.tick();
+            self.ppu_clock += divider;
         }
+    }
 
-        0
+    fn sync_ppu(&mut self) {
+        if self.catch_up {
+            self.run_ppu(self.now);
+            self.synced = true;
+        }
     }
 
-    pub fn write(&mut self, addr: usize, value: u8) {
-        let addr = addr & 0xffff;
+    // rebuilds the page table for whatever is plugged in
+    fn decode(&mut self) {
+        let prg_rom_size = self.cartridge.as_ref().map_or(0, |cartridge| cartridge.prg_rom.len());
+
+        for (page, decoded) in self.pages.iter_mut().enumerate() {
+            let addr = page * PAGE_SIZE;
 
-        for writer in self.writers.iter() {
-            if writer.borrow_mut().write(addr, value) {
-                break;
+            *decoded = match addr {
+                0x0000..=0x1fff => Page::Ram,
+                0x2000..=0x3fff => Page::Ppu,
+                0x4000..=0x40ff => Page::Io,
+                _ if prg_rom_size == 0 => Page::Open,
+                // 16k roms are mirrored into both halves
+                0x8000..=0xffff => Page::PrgRom((addr - 0x8000) % prg_rom_size),
+                _ => Page::Cartridge,
+            };
+        }
+
+        for (index, (range, _)) in self.devices.iter().enumerate() {
+            for page in (range.start() & 0xffff) / PAGE_SIZE..=(range.end() & 0xffff) / PAGE_SIZE {
+                self.pages[page] = Page::Device(index);
             }
         }
     }
-}
 
-pub struct Bus {
-    pub interface: Rc<RefCell<BusInterface>>,
+    fn apu_status(&self) -> u8 {
+        ((self.irq & IRQ_FRAME_COUNTER != 0) as u8) << 6
+    }
 
-    pub cpu: Rc<RefCell<Cpu>>,
-    pub ppu: Rc<RefCell<Ppu>>,
-    pub ram: Rc<RefCell<Memory>>,
+    fn log(&mut self, addr: usize, value: u8, write: bool) {
+        if let Some(log) = &mut self.log {
+            log.push(Access { addr: addr as u16, value, write });
+        }
+    }
 }
 
-// read()
+impl CpuBus for Bus {
+    fn read(&mut self, addr: usize) -> u8 {
+        let addr = addr & 0xffff;
 
-impl Bus {
-    pub fn new() -> Bus {
-        let interface = BusInterface::new();
+        let value = match self.pages[addr / PAGE_SIZE] {
+            Page::Open => None,
+            Page::Ram => Some(self.ram.data[addr & (memory::SIZE - 1)]),
+            Page::Ppu => {
+                self.sync_ppu();
+                Some(self.ppu.read(self.cartridge.as_ref(), addr))
+            },
+            Page::Io => match addr {
+                // only the frame counter's interrupt is emulated, reading
+                // acknowledges it
+                APU_STATUS => {
+                    let value = self.apu_status();
+                    self.irq &= !IRQ_FRAME_COUNTER;
+                    Some(value)
+                },
+                _ => match self.input.read(addr) {
+                    Some(value) => Some(value),
+                    None => self.cartridge.as_mut().and_then(|cartridge| cartridge.read(addr)),
+                },
+            }.map(|value| value | (self.open_bus & undriven(addr))),
+            Page::Cartridge => self.cartridge.as_mut().and_then(|cartridge| cartridge.read(addr)),
+            Page::PrgRom(offset) => {
+                self.cartridge.as_ref().map(|cartridge| cartridge.prg_rom[offset + addr % PAGE_SIZE])
+            },
+            Page::Device(index) => self.devices[index].1.read(addr),
+        };
+
+        let value = value.unwrap_or(self.open_bus);
+
+        // $4015 is inside the cpu, its value never reaches the data bus
+        if addr != APU_STATUS {
+            self.open_bus = value;
+        }
+
+        self.log(addr, value, false);
+        value
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        let addr = addr & 0xffff;
 
-        let cpu = Rc::new(RefCell::new(Cpu::new(interface.clone())));
-        let ppu = Rc::new(RefCell::new(Ppu::new()));
-        let ram = Rc::new(RefCell::new(Memory::new()));
+        match self.pages[addr / PAGE_SIZE] {
+            Page::Open => {},
+            Page::Ram => self.ram.data[addr & (memory::SIZE - 1)] = value,
+            Page::Ppu => {
+                self.sync_ppu();
+                self.ppu.write(self.cartridge.as_mut(), addr, value);
+            },
+            Page::Io => match addr {
+                OAM_DMA => self.dma = Some(value),
+                FRAME_COUNTER => {
+                    self.frame_counter.write(value);
 
-        {
-            let mut bus = interface.borrow_mut();
+                    if self.frame_counter.irq_inhibit {
+                        self.irq &= !IRQ_FRAME_COUNTER;
+                    }
+                },
+                _ => {
+                    if !self.input.write(addr, value) {
+                        if let Some(cartridge) = &mut self.cartridge {
+                            cartridge.write(addr, value);
+                        }
+                    }
+                },
+            },
+            Page::Cartridge | Page::PrgRom(_) => {
+                // mapper registers can change what the ppu sees
+                if addr >= 0x8000 {
+                    self.sync_ppu();
+                }
 
-            // add ram to bus
-            bus.readers.push(ram.clone());
-            bus.writers.push(ram.clone());
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.write(addr, value);
+                }
+            },
+            Page::Device(index) => {
+                self.devices[index].1.write(addr, value);
+            },
         }
 
-        Bus { interface, cpu, ppu, ram }
+        self.open_bus = value;
+        self.log(addr, value, true);
     }
 
-    pub fn read(&self, addr: usize) -> u8 {
-        self.interface.borrow().read(addr)
+    // reads without side effects and without being logged
+    fn peek(&self, addr: usize) -> u8 {
+        let addr = addr & 0xffff;
+        let cartridge = self.cartridge.as_ref();
+
+        let value = match self.pages[addr / PAGE_SIZE] {
+            Page::Open => None,
+            Page::Ram => Some(self.ram.data[addr & (memory::SIZE - 1)]),
+            Page::Ppu => Some(self.ppu.peek(cartridge, addr)),
+            Page::Io => match addr {
+                APU_STATUS => Some(self.apu_status()),
+                _ => self.input.peek(addr).or_else(|| cartridge?.peek(addr)),
+            }.map(|value| value | (self.open_bus & undriven(addr))),
+            Page::Cartridge => cartridge.and_then(|cartridge| cartridge.peek(addr)),
+            Page::PrgRom(offset) => cartridge.map(|cartridge| cartridge.prg_rom[offset + addr % PAGE_SIZE]),
+            Page::Device(index) => self.devices[index].1.peek(addr),
+        };
+
+        value.unwrap_or(self.open_bus)
     }
+}
+
+impl Snapshot for Bus {
+    fn save(&self, w: &mut StateWriter) {
+        self.ppu.save(w);
+        self.ram.save(w);
+        self.input.save(w);
+        w.write_u8(self.open_bus);
+        self.frame_counter.save(w);
+        w.write_u8(self.irq);
+        w.write_bool(self.dma.is_some());
+        w.write_u8(self.dma.unwrap_or(0));
+        w.write_u64(self.ppu_clock);
 
-    pub fn write(&mut self, addr: usize, value: u8) {
-        self.interface.borrow_mut().write(addr, value)
+        w.write_bool(self.cartridge.is_some());
+
+        if let Some(cartridge) = &self.cartridge {
+            cartridge.save(w);
+        }
     }
-}
\ No newline at end of file
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ppu.load(r)?;
+        self.ram.load(r)?;
+        self.input.load(r)?;
+        self.open_bus = r.read_u8()?;
+        self.frame_counter.load(r)?;
+        self.irq = r.read_u8()?;
+
+        let dma = r.read_bool()?;
+        let page = r.read_u8()?;
+
+        self.dma = if dma { Some(page) } else { None };
+        self.ppu_clock = r.read_u64()?;
+
+        if r.read_bool()? != self.cartridge.is_some() {
+            return Err(StateError::Invalid("cartridge"));
+        }
+
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.load(r)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Bus {
+        Bus::new()
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+use super::{
+    bus::{BusRead, BusWrite},
+    region::Region,
+    state::{Snapshot, StateReader, StateWriter, StateError},
+};
+
+pub const HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+pub const PRG_BANK_SIZE: usize = 16 * 1024;
+pub const CHR_BANK_SIZE: usize = 8 * 1024;
+pub const PRG_RAM_SIZE: usize = 8 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CartridgeError {
+    BadMagic,
+    Truncated,
+    UnsupportedMapper(u8),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::BadMagic => write!(f, "not an iNES rom"),
+            CartridgeError::Truncated => write!(f, "rom is truncated"),
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "mapper {} is not supported", mapper),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+impl From<CartridgeError> for JsValue {
+    fn from(error: CartridgeError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+}
+
+#[derive(Clone)]
+pub struct Cartridge {
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub prg_rom: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    pub chr: Vec<u8>,
+    pub chr_ram: bool,
+    // only nes 2.0 headers say, and not for games that run on either
+    pub region: Option<Region>,
+
+    // set when battery backed ram changes, cleared once persisted
+    pub sram_dirty: bool,
+}
+
+impl Cartridge {
+    pub fn new(rom: &[u8]) -> Result<Cartridge, CartridgeError> {
+        if rom.len() < HEADER_SIZE {
+            return Err(CartridgeError::Truncated);
+        }
+
+        if rom[0..4] != *b"NES\x1a" {
+            return Err(CartridgeError::BadMagic);
+        }
+
+        let prg_size = rom[4] as usize * PRG_BANK_SIZE;
+        let chr_size = rom[5] as usize * CHR_BANK_SIZE;
+        let flags6 = rom[6];
+        let flags7 = rom[7];
+
+        let mapper = (flags7 & 0xf0) | (flags6 >> 4);
+        let nes2 = flags7 & 0b00001100 == 0b00001000;
+        let battery = flags6 & 0b00000010 != 0;
+        let trainer = flags6 & 0b00000100 != 0;
+
+        let mirroring = if flags6 & 0b00001000 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0b00000001 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        if mapper != 0 {
+            return Err(CartridgeError::UnsupportedMapper(mapper));
+        }
+
+        let prg_start = HEADER_SIZE + if trainer { TRAINER_SIZE } else { 0 };
+        let chr_start = prg_start + prg_size;
+
+        if prg_size == 0 || rom.len() < chr_start + chr_size {
+            return Err(CartridgeError::Truncated);
+        }
+
+        let prg_rom = rom[prg_start..chr_start].to_vec();
+
+        let region = if nes2 {
+            Region::from_u8(rom[12] & 0b00000011)
+        } else {
+            None
+        };
+
+        // boards without chr rom have 8k of chr ram instead
+        let (chr, chr_ram) = if chr_size == 0 {
+            (vec![0; CHR_BANK_SIZE], true)
+        } else {
+            (rom[chr_start..chr_start + chr_size].to_vec(), false)
+        };
+
+        Ok(Cartridge {
+            mapper,
+            mirroring,
+            battery,
+            prg_rom,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr,
+            chr_ram,
+            region,
+            sram_dirty: false,
+        })
+    }
+
+    pub fn sram(&self) -> Option<&[u8]> {
+        if self.battery {
+            Some(&self.prg_ram)
+        } else {
+            None
+        }
+    }
+
+    pub fn load_sram(&mut self, sram: &[u8]) -> bool {
+        if !self.battery || sram.len() != self.prg_ram.len() {
+            return false;
+        }
+
+        self.prg_ram.copy_from_slice(sram);
+        self.sram_dirty = false;
+
+        true
+    }
+}
+
+impl BusRead for Cartridge {
+    fn read(&mut self, addr: usize) -> Option<u8> {
+        self.peek(addr)
+    }
+
+    fn peek(&self, addr: usize) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff => Some(self.prg_ram[addr - 0x6000]),
+            // 16k roms are mirrored into both halves
+            0x8000..=0xffff => Some(self.prg_rom[(addr - 0x8000) % self.prg_rom.len()]),
+            _ => None,
+        }
+    }
+}
+
+impl BusWrite for Cartridge {
+    fn write(&mut self, addr: usize, value: u8) -> bool {
+        match addr {
+            0x6000..=0x7fff => {
+                let ram = &mut self.prg_ram[addr - 0x6000];
+
+                if *ram != value {
+                    *ram = value;
+                    self.sram_dirty |= self.battery;
+                }
+
+                true
+            },
+            // nrom has no registers, writes to rom are ignored
+            0x8000..=0xffff => true,
+            _ => false,
+        }
+    }
+}
+
+impl Snapshot for Cartridge {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram);
+
+        if self.chr_ram {
+            w.write_bytes(&self.chr);
+        }
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.read_into(&mut self.prg_ram)?;
+
+        if self.chr_ram {
+            r.read_into(&mut self.chr)?;
+        }
+
+        // restored ram no longer matches what was persisted
+        self.sram_dirty = self.battery;
+
+        Ok(())
+    }
+}
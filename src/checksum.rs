@@ -0,0 +1,27 @@
+// fnv-1a, 64 bit. not for anything adversarial, but it's the same on
+// every platform and build, so it can be written into tests
+pub fn fnv1a64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// crc-32 (ieee 802.3), the same variant used by zip and png
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+
+    !crc
+}
@@ -1,9 +1,13 @@
-use std::rc::Rc;
-use std::cell::RefCell;
-use super::{Tick, bus::BusInterface};
+use super::{
+    bus::CpuBus,
+    opcodes::{self, Mode},
+    state::{Snapshot, StateReader, StateWriter, StateError},
+};
 
+pub const OAM_DMA: usize = 0x4014;
+
+#[derive(Clone)]
 pub struct Cpu {
-    pub bus: Rc<RefCell<BusInterface>>,
     pub pc: u16,
     pub sp: u8,
     pub a: u8,
@@ -13,27 +17,15 @@ pub struct Cpu {
     pub p: u8,
     pub addr: usize,
     pub skip_ticks: u64,
-    pub debug: String,
+    pub cycles: u64,
+    // nmi is edge triggered and cleared once serviced, irq is a level
+    pub nmi: bool,
+    pub irq: bool,
+    // the opcode the cpu stopped on, one that jams a real 6502 or one that
+    // isn't emulated yet. nothing runs until the next reset
+    pub halted: Option<u8>,
 }
 
-#[derive(PartialEq)]
-enum Mode {
-    Accumulator,
-    Implied,
-    Immediate,
-    ZeroPage,
-    ZeroPageX,
-    ZeroPageY,
-    Relative,
-    Absolute,
-    AbsoluteX,
-    AbsoluteY,
-    Indirect,
-    IndirectX,
-    IndirectY,
-}
-
-
 #[derive(PartialEq)]
 enum Interrupt {
     Nmi,
@@ -42,6 +34,16 @@ enum Interrupt {
     Break,
 }
 
+impl Interrupt {
+    fn vector(&self) -> usize {
+        match self {
+            Interrupt::Nmi => 0xFFFA,
+            Interrupt::Reset => 0xFFFC,
+            Interrupt::Irq | Interrupt::Break => 0xFFFE,
+        }
+    }
+}
+
 #[derive(PartialEq)]
 enum Flag {
     Carry = 0b00000001,
@@ -54,12 +56,9 @@ enum Flag {
     Negative = 0b10000000,
 }
 
-type Instruction = (&'static str, Mode, u64);
-
 impl Cpu {
-    pub fn new(bus: Rc<RefCell<BusInterface>>) -> Cpu {
+    pub fn new() -> Cpu {
         Cpu {
-            bus,
             pc: 0, // program counter
             sp: 0, // stack pointer
             a: 0,
@@ -69,65 +68,119 @@ impl Cpu {
             p: 0, // processor flags
             addr: 0,
             skip_ticks: 0,
-            debug: String::new(),
+            cycles: 0,
+            nmi: false,
+            irq: false,
+            halted: None,
         }
     }
 
-    pub fn reset(&mut self) {
-        self.pc = self.read_word(0xFFFC);
-        self.skip_ticks = 8;
+    pub fn reset<B: CpuBus>(&mut self, bus: &mut B) {
+        self.pc = self.read_word(bus, Interrupt::Reset.vector());
+        self.set_flag(Flag::InterruptDisable, true);
+        self.skip_ticks = 7;
+        self.halted = None;
     }
 
-    fn write(&mut self, addr: usize, value: u8) {
-        self.bus.borrow_mut().write(addr, value);
+    // runs one cpu cycle against `bus`
+    pub fn tick<B: CpuBus>(&mut self, bus: &mut B) {
+        self.cycles += 1;
+
+        if self.halted.is_some() {
+            return;
+        }
+
+        if self.skip_ticks > 0 {
+            self.skip_ticks -= 1;
+            return;
+        }
+
+        // interrupts take the place of the next instruction, 7 cycles
+        if self.nmi {
+            self.nmi = false;
+            self.interrupt(bus, Interrupt::Nmi);
+            self.skip_ticks = 6;
+            return;
+        }
+
+        if self.interrupt_pending() {
+            self.interrupt(bus, Interrupt::Irq);
+            self.skip_ticks = 6;
+            return;
+        }
+
+        let opcode = self.next(bus) as usize;
+        self.execute(bus, opcode);
     }
 
-    fn read(&self, addr: usize) -> u8 {
-        self.bus.borrow().read(addr)
+    // whether the next instruction boundary services an interrupt instead
+    pub fn interrupt_pending(&self) -> bool {
+        self.nmi || (self.irq && !self.get_flag(Flag::InterruptDisable))
     }
 
-    fn read_word(&self, addr: usize) -> u16 {
-        let lo = self.read(addr) as u16;
-        let hi = self.read(addr + 1) as u16;
+    fn interrupt<B: CpuBus>(&mut self, bus: &mut B, interrupt: Interrupt) {
+        self.push_word(bus, self.pc);
 
-        (hi << 8) | lo
+        // only the pushed copy of p has the unused bit set, and the break
+        // bit tells brk apart from a hardware irq
+        let mut p = self.p | Flag::Push as u8;
+
+        if interrupt == Interrupt::Break {
+            p |= Flag::Break as u8;
+        }
+
+        self.push(bus, p);
+        self.set_flag(Flag::InterruptDisable, true);
+
+        self.pc = self.read_word(bus, interrupt.vector());
     }
 
-    fn next(&mut self) -> u8 {
-        let byte = self.read(self.pc as usize);
-        self.pc = self.pc.overflowing_add(1).0;
-        byte
+    // copies a page to $2004, the cpu is stalled for 513 cycles plus one
+    // more when it starts on an odd cycle. the machine runs it as an event
+    // once the cpu has written to $4014
+    pub fn oam_dma<B: CpuBus>(&mut self, bus: &mut B, page: u8) {
+        let base = (page as usize) << 8;
+
+        for i in 0..256 {
+            let value = bus.read(base + i);
+            bus.write(0x2004, value);
+        }
+
+        self.skip_ticks += 513 + (self.cycles & 1);
     }
 
-    fn next_word(&mut self) -> u16 {
-        let lo = self.next() as u16;
-        let hi = self.next() as u16;
+    fn read_word<B: CpuBus>(&mut self, bus: &mut B, addr: usize) -> u16 {
+        let lo = bus.read(addr) as u16;
+        let hi = bus.read(addr + 1) as u16;
 
         (hi << 8) | lo
     }
 
-    fn push(&mut self, value: u8) {
-        let addr = 0x0100 + (self.sp as u16);
-        self.write(addr as usize, value);
-        self.sp = self.sp.wrapping_sub(1);
+    fn next<B: CpuBus>(&mut self, bus: &mut B) -> u8 {
+        let byte = bus.read(self.pc as usize);
+        self.pc = self.pc.overflowing_add(1).0;
+        byte
     }
 
-    fn push_word(&mut self, value: u16) {
-        self.push((value >> 8) as u8);
-        self.push(value as u8);
+    fn next_word<B: CpuBus>(&mut self, bus: &mut B) -> u16 {
+        let lo = self.next(bus) as u16;
+        let hi = self.next(bus) as u16;
+
+        (hi << 8) | lo
     }
 
-    fn pop(&mut self) -> u8 {
-        self.sp = self.sp.wrapping_add(1);
+    fn push<B: CpuBus>(&mut self, bus: &mut B, value: u8) {
         let addr = 0x0100 + (self.sp as u16);
-        self.read(addr as usize)
+        bus.write(addr as usize, value);
+        self.sp = self.sp.wrapping_sub(1);
     }
 
-    fn pop_word(&mut self) -> u16 {
-        (self.pop() as u16) | ((self.pop() as u16) << 8)
+    fn push_word<B: CpuBus>(&mut self, bus: &mut B, value: u16) {
+        self.push(bus, (value >> 8) as u8);
+        self.push(bus, value as u8);
     }
 
-    fn read_operand_address(&mut self, mode: Mode) -> (usize, bool) {
+    fn read_operand_address<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> (usize, bool) {
         match mode {
             Mode::Immediate => {
                 let addr = self.pc as usize;
@@ -135,9 +188,9 @@ impl Cpu {
 
                 (addr, false)
             },
-            Mode::ZeroPage => (self.next() as usize, false),
+            Mode::ZeroPage => (self.next(bus) as usize, false),
             Mode::ZeroPageX => {
-                let mut addr = self.next() as u16;
+                let mut addr = self.next(bus) as u16;
 
                 addr += self.x as u16;
                 addr &= 0x00ff;
@@ -145,7 +198,7 @@ impl Cpu {
                 (addr as usize, false)
             },
             Mode::ZeroPageY => {
-                let mut addr = self.next() as u16;
+                let mut addr = self.next(bus) as u16;
 
                 addr += self.y as u16;
                 addr &= 0x00ff;
@@ -153,49 +206,49 @@ impl Cpu {
                 (addr as usize, false)
             },
             Mode::Relative => {
-                let mut addr = self.next() as u16;
+                let mut offset = self.next(bus) as u16;
 
-                if addr & 0b10000000 != 0 {
-                    addr |= 0xff00;
+                if offset & 0b10000000 != 0 {
+                    offset |= 0xff00;
                 }
 
-                (addr as usize, false)
+                // branch targets are relative to the next instruction
+                (self.pc.wrapping_add(offset) as usize, false)
             },
-            Mode::Absolute => (self.next_word() as usize, false),
+            Mode::Absolute => (self.next_word(bus) as usize, false),
             Mode::AbsoluteX => {
-                let lo = self.next() as u16;
-                let hi = self.next() as u16;
+                let lo = self.next(bus) as u16;
+                let hi = self.next(bus) as u16;
                 let mut addr = (hi << 8) | lo;
 
-                // add x to absolute address
-                addr += self.x as u16;
+                // add x to absolute address, $ffff wraps around to $0000
+                addr = addr.wrapping_add(self.x as u16);
 
                 // checks whether page has changed
                 (addr as usize, addr & 0xff00 != hi << 8)
             },
             Mode::AbsoluteY => {
-                let lo = self.next() as u16;
-                let hi = self.next() as u16;
+                let lo = self.next(bus) as u16;
+                let hi = self.next(bus) as u16;
                 let mut addr = (hi << 8) | lo;
 
-                // add y to absolute address
-                addr += self.y as u16;
+                // add y to absolute address, $ffff wraps around to $0000
+                addr = addr.wrapping_add(self.y as u16);
 
                 // checks whether page has changed
                 (addr as usize, addr & 0xff00 != hi << 8)
             },
             Mode::Indirect => {
-                let mut lo = self.next() as u16;
-                let mut hi = self.next() as u16;
+                let mut lo = self.next(bus) as u16;
+                let mut hi = self.next(bus) as u16;
                 let mut addr = (hi << 8) | lo;
-                let bus = self.bus.borrow();
 
                 // simulate page boundary hardware bug
                 if lo == 0x00ff {
-                    lo = bus.read((addr + 0) as usize) as u16;
+                    lo = bus.read(addr as usize) as u16;
                     hi = bus.read((addr & 0xff00) as usize) as u16;
                 } else {
-                    lo = bus.read((addr + 0) as usize) as u16;
+                    lo = bus.read(addr as usize) as u16;
                     hi = bus.read((addr + 1) as usize) as u16;
                 }
 
@@ -204,9 +257,8 @@ impl Cpu {
                 (addr as usize, false)
             },
             Mode::IndirectX => {
-                let mut addr = self.next() as u16;
-                let bus = self.bus.borrow();
-                let lo = bus.read(((addr + (self.x as u16) + 0) & 0x00FF) as usize) as u16;
+                let mut addr = self.next(bus) as u16;
+                let lo = bus.read(((addr + (self.x as u16)) & 0x00FF) as usize) as u16;
                 let hi = bus.read(((addr + (self.x as u16) + 1) & 0x00FF) as usize) as u16;
 
                 addr = (hi << 8) | lo;
@@ -214,13 +266,12 @@ impl Cpu {
                 (addr as usize, false)
             },
             Mode::IndirectY => {
-                let mut addr = self.next() as u16;
-                let bus = self.bus.borrow();
-                let lo = bus.read(((addr + 0) & 0x00ff) as usize) as u16;
+                let mut addr = self.next(bus) as u16;
+                let lo = bus.read((addr & 0x00ff) as usize) as u16;
                 let hi = bus.read(((addr + 1) & 0x00ff) as usize) as u16;
 
-                // create addr from bytes
-                addr = (hi << 8) | lo;
+                // create addr from bytes, then index it with y
+                addr = ((hi << 8) | lo).wrapping_add(self.y as u16);
 
                 (addr as usize, addr & 0xff00 != hi << 8)
             }
@@ -228,131 +279,46 @@ impl Cpu {
         }
     }
 
-    fn get_instruction(opcode: usize) -> Instruction {
-        match opcode {
-            // ADC
-            0x69 => ("ADC", Mode::Immediate, 2),
-            0x65 => ("ADC", Mode::ZeroPage, 3),
-            0x75 => ("ADC", Mode::ZeroPageX, 4),
-            0x6d => ("ADC", Mode::Absolute, 4),
-            0x7d => ("ADC", Mode::AbsoluteX, 4),
-            0x79 => ("ADC", Mode::AbsoluteY, 4),
-            0x61 => ("ADC", Mode::IndirectX, 6),
-            0x71 => ("ADC", Mode::IndirectY, 5),
-
-            // AND
-            0x29 => ("AND", Mode::Immediate, 2),
-            0x25 => ("AND", Mode::ZeroPage, 3),
-            0x35 => ("AND", Mode::ZeroPageX, 4),
-            0x2d => ("AND", Mode::Absolute, 4),
-            0x3d => ("AND", Mode::AbsoluteX, 4),
-            0x39 => ("AND", Mode::AbsoluteY, 4),
-            0x21 => ("AND", Mode::IndirectX, 6),
-            0x31 => ("AND", Mode::IndirectY, 5),
-
-            // ASL
-            0x01 => ("ASL", Mode::Accumulator, 2),
-            0x06 => ("ASL", Mode::ZeroPage, 5),
-            0x16 => ("ASL", Mode::ZeroPageX, 6),
-            0x0e => ("ASL", Mode::Absolute, 6),
-            0x1e => ("ASL", Mode::AbsoluteX, 7),
-
-            // BCC
-            0x90 => ("BCC", Mode::Relative, 2),
-
-            // BCS
-            0xb0 => ("BCS", Mode::Relative, 2),
-
-            // BEQ
-            0xf0 => ("BEQ", Mode::Relative, 2),
-
-            // BIT
-            0x24 => ("BIT", Mode::ZeroPage, 3),
-            0x2c => ("BIT", Mode::Absolute, 4),
-
-            // BMI
-            0x30 => ("BMI", Mode::Relative, 2),
-
-            // BNE
-            0xd0 => ("BNE", Mode::Relative, 2),
-
-            // BPL
-            0x10 => ("BPL", Mode::Relative, 2),
-
-            // BRK
-            0x00 => ("BRK", Mode::Implied, 7),
-
-            // BVC
-            0x50 => ("BVC", Mode::Relative, 2),
-
-            // BVS
-            0x70 => ("BVS", Mode::Relative, 2),
-
-            // Clear Flags
-            0x18 => ("CLC", Mode::Implied, 2),
-            0xd8 => ("CLD", Mode::Implied, 2),
-            0x58 => ("CLI", Mode::Implied, 2),
-            0xb8 => ("CLV", Mode::Implied, 2),
-
-            // CMP
-            0xc9 => ("CMP", Mode::Immediate, 2),
-            0xc5 => ("CMP", Mode::ZeroPage, 3),
-            0xd5 => ("CMP", Mode::ZeroPageX, 4),
-            0xcd => ("CMP", Mode::Absolute, 4),
-            0xdd => ("CMP", Mode::AbsoluteX, 4),
-            0xd9 => ("CMP", Mode::AbsoluteY, 4),
-            0xc1 => ("CMP", Mode::IndirectX, 6),
-            0xd1 => ("CMP", Mode::IndirectY, 5),
-
-            // CPX
-            0xe0 => ("CPX", Mode::Immediate, 2),
-            0xe4 => ("CPX", Mode::ZeroPage, 3),
-            0xec => ("CPX", Mode::Absolute, 4),
-
-            // CPY
-            0xc0 => ("CPY", Mode::Immediate, 2),
-            0xc4 => ("CPY", Mode::ZeroPage, 3),
-            0xcc => ("CPY", Mode::Absolute, 4),
-
-            _ => panic!("Unknown opcode: {:#04x}", opcode),
-        }
-    }
+    fn execute<B: CpuBus>(&mut self, bus: &mut B, opcode: usize) {
+        let (name, mode, skip_ticks) = match opcodes::get(opcode) {
+            Some(instruction) => instruction,
+            None => {
+                // left pointing at the opcode, like a debugger would show it
+                self.pc = self.pc.wrapping_sub(1);
+                self.halted = Some(opcode as u8);
+                return;
+            },
+        };
 
-    fn execute(&mut self, opcode: usize) {
-        let (name, mode, skip_ticks) = Self::get_instruction(opcode);
+        // this tick is the first cycle of the instruction
+        self.skip_ticks = skip_ticks - 1;
 
         if match name {
-            "ADC" => self.adc(mode),
-            "AND" => self.and(mode),
-            "ASL" => self.asl(mode),
-            "BCC" => self.bcc(),
-            "BCS" => self.bcs(),
-            "BEQ" => self.beq(),
-            "BIT" => self.bit(mode),
-            "BMI" => self.bmi(),
-            "BNE" => self.bne(),
-            "BPL" => self.bpl(),
-            "BRK" => self.brk(),
-            "BVC" => self.bvc(),
-            "BVS" => self.bvs(),
+            "ADC" => self.adc(bus, mode),
+            "AND" => self.and(bus, mode),
+            "ASL" => self.asl(bus, mode),
+            "BCC" => self.bcc(bus),
+            "BCS" => self.bcs(bus),
+            "BEQ" => self.beq(bus),
+            "BIT" => self.bit(bus, mode),
+            "BMI" => self.bmi(bus),
+            "BNE" => self.bne(bus),
+            "BPL" => self.bpl(bus),
+            "BRK" => self.brk(bus),
+            "BVC" => self.bvc(bus),
+            "BVS" => self.bvs(bus),
             "CLC" => self.clc(),
             "CLD" => self.cld(),
             "CLI" => self.cli(),
             "CLV" => self.clv(),
-            "CMP" => self.cmp(mode),
-            "CPX" => self.cpx(mode),
-            "CPY" => self.cpy(mode),
+            "CMP" => self.cmp(bus, mode),
+            "CPX" => self.cpx(bus, mode),
+            "CPY" => self.cpy(bus, mode),
             _ => false,
         } {
             // add additional tick
             self.skip_ticks += 1;
         }
-
-        // add instruction skip ticks
-        self.skip_ticks = skip_ticks;
-
-        self.debug.clear();
-        self.debug.push_str(name);
     }
 
     fn set_flag(&mut self, flag: Flag, value: bool) {
@@ -369,9 +335,9 @@ impl Cpu {
         self.p & (flag as u8) > 0
     }
 
-    fn adc(&mut self, mode: Mode) -> bool {
-        let (addr, skip_tick) = self.read_operand_address(mode);
-        let operand = self.read(addr);
+    fn adc<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
+        let (addr, skip_tick) = self.read_operand_address(bus, mode);
+        let operand = bus.read(addr);
 
         let value = (self.a as u16)
             + (operand as u16)
@@ -384,7 +350,7 @@ impl Cpu {
         self.set_flag(Flag::Overflow,
             (
                 !((self.a as u16) ^ (operand as u16))
-                & ((self.a as u16) ^ (value as u16))
+                & ((self.a as u16) ^ value)
             )
             & 0x0080 != 0
         );
@@ -397,19 +363,19 @@ impl Cpu {
         skip_tick
     }
 
-    fn and(&mut self, mode: Mode) -> bool {
-        let (addr, skip_tick) = self.read_operand_address(mode);
-        let operand = self.read(addr);
+    fn and<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
+        let (addr, skip_tick) = self.read_operand_address(bus, mode);
+        let operand = bus.read(addr);
 
         self.a &= operand;
 
-        self.set_flag(Flag::Zero, (self.a & 0x00ff) == 0);
+        self.set_flag(Flag::Zero, self.a == 0);
         self.set_flag(Flag::Negative, (self.a & 0b10000000) != 0);
 
         skip_tick
     }
 
-    fn asl(&mut self, mode: Mode) -> bool {
+    fn asl<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
         let value = if mode == Mode::Accumulator {
             let value = (self.a as u16) << 1;
 
@@ -419,11 +385,11 @@ impl Cpu {
             // return value for flags
             value
         } else {
-            let addr = self.read_operand_address(mode).0;
-            let operand = self.read(addr);
+            let addr = self.read_operand_address(bus, mode).0;
+            let operand = bus.read(addr);
             let value = (operand as u16) << 1;
 
-            self.write(addr, (value & 0x00ff) as u8);
+            bus.write(addr, (value & 0x00ff) as u8);
 
             value
         };
@@ -435,8 +401,8 @@ impl Cpu {
         false
     }
 
-    fn branch(&mut self, cond: bool) {
-        let addr = self.read_operand_address(Mode::Relative).0;
+    fn branch<B: CpuBus>(&mut self, bus: &mut B, cond: bool) {
+        let addr = self.read_operand_address(bus, Mode::Relative).0;
 
         if cond {
             self.skip_ticks += 1;
@@ -449,24 +415,24 @@ impl Cpu {
         }
     }
 
-    fn bcc(&mut self) -> bool {
-        self.branch(!self.get_flag(Flag::Carry));
+    fn bcc<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, !self.get_flag(Flag::Carry));
         false
     }
 
-    fn bcs(&mut self) -> bool {
-        self.branch(self.get_flag(Flag::Carry));
+    fn bcs<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, self.get_flag(Flag::Carry));
         false
     }
 
-    fn beq(&mut self) -> bool {
-        self.branch(self.get_flag(Flag::Zero));
+    fn beq<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, self.get_flag(Flag::Zero));
         false
     }
 
-    fn bit(&mut self, mode: Mode) -> bool {
-        let addr = self.read_operand_address(mode).0;
-        let operand = self.read(addr);
+    fn bit<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
+        let addr = self.read_operand_address(bus, mode).0;
+        let operand = bus.read(addr);
         let value = self.a & operand;
 
         self.set_flag(Flag::Zero, value == 0);
@@ -476,43 +442,35 @@ impl Cpu {
         false
     }
 
-    fn bmi(&mut self) -> bool {
-        self.branch(self.get_flag(Flag::Negative));
+    fn bmi<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, self.get_flag(Flag::Negative));
         false
     }
 
-    fn bne(&mut self) -> bool {
-        self.branch(!self.get_flag(Flag::Zero));
+    fn bne<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, !self.get_flag(Flag::Zero));
         false
     }
 
-    fn bpl(&mut self) -> bool {
-        self.branch(!self.get_flag(Flag::Negative));
+    fn bpl<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, !self.get_flag(Flag::Negative));
         false
     }
 
-    fn brk(&mut self) -> bool {
-        self.next();
-
-        self.set_flag(Flag::InterruptDisable, true);
-        self.push_word(self.pc);
-
-        self.set_flag(Flag::Break, true);
-        self.push(self.s);
-        self.set_flag(Flag::Break, false);
-
-        self.pc = self.read_word(0xFFFE);
+    fn brk<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.next(bus);
+        self.interrupt(bus, Interrupt::Break);
 
         false
     }
 
-    fn bvc(&mut self) -> bool {
-        self.branch(!self.get_flag(Flag::Overflow));
+    fn bvc<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, !self.get_flag(Flag::Overflow));
         false
     }
 
-    fn bvs(&mut self) -> bool {
-        self.branch(self.get_flag(Flag::Overflow));
+    fn bvs<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        self.branch(bus, self.get_flag(Flag::Overflow));
         false
     }
 
@@ -536,10 +494,10 @@ impl Cpu {
         false
     }
 
-    fn cmp(&mut self, mode: Mode) -> bool {
-        let (addr, skip_tick) = self.read_operand_address(mode);
-        let operand = self.read(addr);
-        let value = (self.a as u16) - (operand as u16);
+    fn cmp<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
+        let (addr, skip_tick) = self.read_operand_address(bus, mode);
+        let operand = bus.read(addr);
+        let value = (self.a as u16).wrapping_sub(operand as u16);
 
         self.set_flag(Flag::Carry, self.a >= operand);
         self.set_flag(Flag::Zero, (value & 0x00ff) == 0);
@@ -548,10 +506,10 @@ impl Cpu {
         skip_tick
     }
 
-    fn cpx(&mut self, mode: Mode) -> bool {
-        let (addr, skip_tick) = self.read_operand_address(mode);
-        let operand = self.read(addr);
-        let value = (self.x as u16) - (operand as u16);
+    fn cpx<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
+        let (addr, skip_tick) = self.read_operand_address(bus, mode);
+        let operand = bus.read(addr);
+        let value = (self.x as u16).wrapping_sub(operand as u16);
 
         self.set_flag(Flag::Carry, self.x >= operand);
         self.set_flag(Flag::Zero, (value & 0x00ff) == 0);
@@ -560,12 +518,12 @@ impl Cpu {
         skip_tick
     }
 
-    fn cpy(&mut self, mode: Mode) -> bool {
-        let (addr, skip_tick) = self.read_operand_address(mode);
-        let operand = self.read(addr);
-        let value = (self.x as u16) - (operand as u16);
+    fn cpy<B: CpuBus>(&mut self, bus: &mut B, mode: Mode) -> bool {
+        let (addr, skip_tick) = self.read_operand_address(bus, mode);
+        let operand = bus.read(addr);
+        let value = (self.y as u16).wrapping_sub(operand as u16);
 
-        self.set_flag(Flag::Carry, self.x >= operand);
+        self.set_flag(Flag::Carry, self.y >= operand);
         self.set_flag(Flag::Zero, (value & 0x00ff) == 0);
         self.set_flag(Flag::Negative, (value & 0x0080) != 0);
 
@@ -573,14 +531,49 @@ impl Cpu {
     }
 }
 
-impl Tick for Cpu {
-    fn tick(&mut self) {
-        if self.skip_ticks > 0 {
-            self.skip_ticks -= 1;
-            return;
-        }
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
+}
 
-        let opcode = self.next() as usize;
-        self.execute(opcode);
+impl Snapshot for Cpu {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_u16(self.pc);
+        w.write_u8(self.sp);
+        w.write_u8(self.a);
+        w.write_u8(self.x);
+        w.write_u8(self.y);
+        w.write_u8(self.s);
+        w.write_u8(self.p);
+        w.write_u64(self.addr as u64);
+        w.write_u64(self.skip_ticks);
+        w.write_u64(self.cycles);
+        w.write_bool(self.nmi);
+        w.write_bool(self.irq);
+        w.write_bool(self.halted.is_some());
+        w.write_u8(self.halted.unwrap_or(0));
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.pc = r.read_u16()?;
+        self.sp = r.read_u8()?;
+        self.a = r.read_u8()?;
+        self.x = r.read_u8()?;
+        self.y = r.read_u8()?;
+        self.s = r.read_u8()?;
+        self.p = r.read_u8()?;
+        self.addr = r.read_u64()? as usize;
+        self.skip_ticks = r.read_u64()?;
+        self.cycles = r.read_u64()?;
+        self.nmi = r.read_bool()?;
+        self.irq = r.read_bool()?;
+
+        let halted = r.read_bool()?;
+        let opcode = r.read_u8()?;
+
+        self.halted = if halted { Some(opcode) } else { None };
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
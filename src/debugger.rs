@@ -0,0 +1,288 @@
+use std::fmt;
+use std::collections::BTreeMap;
+use super::{
+    bus::{Access, Bus, CpuBus},
+    cpu::Cpu,
+    opcodes,
+    expr::{self, Expr, Var},
+};
+
+const OPCODE_BRK: u8 = 0x00;
+const OPCODE_JSR: u8 = 0x20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BreakReason {
+    Breakpoint(u16),
+    Watchpoint { addr: u16, value: u8, write: bool },
+    Step,
+    Brk(u16),
+    UnknownOpcode { pc: u16, opcode: u8 },
+    Nmi,
+    Irq,
+    Scanline(u16),
+    Pause,
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreakReason::Breakpoint(pc) => write!(f, "breakpoint at ${:04X}", pc),
+            BreakReason::Watchpoint { addr, value, write: true } => {
+                write!(f, "watchpoint: wrote ${:02X} to ${:04X}", value, addr)
+            },
+            BreakReason::Watchpoint { addr, value, write: false } => {
+                write!(f, "watchpoint: read ${:02X} from ${:04X}", value, addr)
+            },
+            BreakReason::Step => write!(f, "step"),
+            BreakReason::Brk(pc) => write!(f, "brk at ${:04X}", pc),
+            BreakReason::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode ${:02X} at ${:04X}", opcode, pc)
+            },
+            BreakReason::Nmi => write!(f, "nmi"),
+            BreakReason::Irq => write!(f, "irq"),
+            BreakReason::Scanline(line) => write!(f, "scanline {}", line),
+            BreakReason::Pause => write!(f, "paused"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub condition: Option<Expr>,
+}
+
+impl Watchpoint {
+    fn matches(&self, access: &Access) -> bool {
+        let kind = if access.write { self.write } else { self.read };
+
+        kind && (self.start..=self.end).contains(&access.addr)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Step {
+    Into,
+    // back at the instruction after the jsr, with the stack unwound
+    Over { pc: u16, sp: u8 },
+    // the stack pointer rises above where it was, i.e. an rts/rti ran
+    Out { sp: u8 },
+    Scanline { line: u16, left: bool },
+}
+
+// what conditions are evaluated against
+struct Scope<'a> {
+    cpu: &'a Cpu,
+    bus: &'a Bus,
+    access: Option<Access>,
+}
+
+impl<'a> expr::Context for Scope<'a> {
+    fn var(&self, var: Var) -> i64 {
+        let cpu = self.cpu;
+
+        match var {
+            Var::A => cpu.a as i64,
+            Var::X => cpu.x as i64,
+            Var::Y => cpu.y as i64,
+            Var::P => cpu.p as i64,
+            Var::Sp => cpu.sp as i64,
+            Var::Pc => cpu.pc as i64,
+            Var::Scanline => self.bus.ppu.scanline as i64,
+            Var::Dot => self.bus.ppu.dot as i64,
+            Var::Cycles => cpu.cycles as i64,
+            Var::Addr => self.access.map_or(0, |access| access.addr as i64),
+            Var::Value => self.access.map_or(0, |access| access.value as i64),
+        }
+    }
+
+    fn memory(&self, addr: u16) -> u8 {
+        self.bus.peek(addr as usize)
+    }
+}
+
+pub struct Debugger {
+    // execute breakpoints, only taken when their condition holds
+    pub breakpoints: BTreeMap<u16, Option<Expr>>,
+    // checked against the bus's access log, which has to be on for them
+    pub watchpoints: Vec<Watchpoint>,
+
+    pub break_on_brk: bool,
+    pub break_on_unknown: bool,
+    pub break_on_nmi: bool,
+    pub break_on_irq: bool,
+
+    // why emulation is stopped, `None` while running
+    pub reason: Option<BreakReason>,
+
+    step: Option<Step>,
+    // lets the instruction we stopped on run when resuming
+    resuming: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: BTreeMap::new(),
+            watchpoints: vec![],
+            break_on_brk: false,
+            break_on_unknown: false,
+            break_on_nmi: false,
+            break_on_irq: false,
+            reason: None,
+            step: None,
+            resuming: false,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.reason.is_some()
+    }
+
+    // whether there's anything to check each cycle
+    pub fn armed(&self) -> bool {
+        !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || self.step.is_some()
+            || self.break_on_brk
+            || self.break_on_unknown
+            || self.break_on_nmi
+            || self.break_on_irq
+    }
+
+    pub fn pause(&mut self) {
+        if self.reason.is_none() {
+            self.reason = Some(BreakReason::Pause);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        // only stops made before an instruction have already looked at it
+        self.resuming = match self.reason.take() {
+            Some(BreakReason::Watchpoint { .. }) | Some(BreakReason::Pause) | None => false,
+            Some(_) => true,
+        };
+
+        self.step = None;
+    }
+
+    pub fn step_into(&mut self) {
+        self.resume();
+        self.step = Some(Step::Into);
+    }
+
+    // runs a jsr through to its return, anything else is a step into
+    pub fn step_over(&mut self, cpu: &Cpu, bus: &Bus) {
+        self.resume();
+
+        let opcode = bus.peek(cpu.pc as usize);
+
+        self.step = Some(if opcode == OPCODE_JSR {
+            Step::Over { pc: cpu.pc.wrapping_add(3), sp: cpu.sp }
+        } else {
+            Step::Into
+        });
+    }
+
+    pub fn step_out(&mut self, cpu: &Cpu) {
+        self.resume();
+        self.step = Some(Step::Out { sp: cpu.sp });
+    }
+
+    // stops at the first instruction on `line`, the next time it's entered
+    pub fn run_to_scanline(&mut self, line: u16) {
+        self.resume();
+        self.step = Some(Step::Scanline { line, left: false });
+    }
+
+    // called on the cycle an instruction (or interrupt) is about to start
+    pub fn check_instruction(&mut self, cpu: &Cpu, bus: &Bus) -> Option<BreakReason> {
+        let scope = Scope { cpu, bus, access: None };
+        let ppu = &bus.ppu;
+        let resuming = std::mem::take(&mut self.resuming);
+
+        if let Some(Step::Scanline { line, left }) = &mut self.step {
+            if ppu.scanline != *line {
+                *left = true;
+            }
+        }
+
+        // the handler's first instruction is the next boundary
+        if cpu.interrupt_pending() {
+            return match cpu.nmi {
+                true if self.break_on_nmi && !resuming => Some(BreakReason::Nmi),
+                false if self.break_on_irq && !resuming => Some(BreakReason::Irq),
+                _ => None,
+            };
+        }
+
+        let opcode = bus.peek(cpu.pc as usize);
+
+        // resuming would only halt the cpu, so this one always stops
+        if self.break_on_unknown && opcodes::get(opcode as usize).is_none() {
+            return Some(BreakReason::UnknownOpcode { pc: cpu.pc, opcode });
+        }
+
+        if resuming {
+            return None;
+        }
+
+        if let Some(condition) = self.breakpoints.get(&cpu.pc) {
+            if condition.as_ref().is_none_or(|condition| condition.test(&scope)) {
+                return Some(BreakReason::Breakpoint(cpu.pc));
+            }
+        }
+
+        if self.break_on_brk && opcode == OPCODE_BRK {
+            return Some(BreakReason::Brk(cpu.pc));
+        }
+
+        let reason = match self.step? {
+            Step::Into => Some(BreakReason::Step),
+            Step::Over { pc, sp } if cpu.pc == pc && cpu.sp == sp => Some(BreakReason::Step),
+            Step::Out { sp } if cpu.sp > sp => Some(BreakReason::Step),
+            Step::Scanline { line, left: true } if ppu.scanline == line => {
+                Some(BreakReason::Scanline(line))
+            },
+            _ => None,
+        };
+
+        if reason.is_some() {
+            self.step = None;
+        }
+
+        reason
+    }
+
+    // called after a cycle ran, the state is the one after the access.
+    // takes whatever the bus logged since the last call
+    pub fn check_watch(&mut self, cpu: &Cpu, bus: &mut Bus) -> Option<BreakReason> {
+        let log = std::mem::take(bus.log.as_mut()?);
+        let bus = &*bus;
+
+        for access in log.iter() {
+            let scope = Scope { cpu, bus, access: Some(*access) };
+
+            for point in self.watchpoints.iter().filter(|point| point.matches(access)) {
+                if point.condition.as_ref().is_none_or(|condition| condition.test(&scope)) {
+                    return Some(BreakReason::Watchpoint {
+                        addr: access.addr,
+                        value: access.value,
+                        write: access.write,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeSet;
+use super::{
+    bus::CpuBus,
+    opcodes::{self, Mode},
+};
+
+// one decoded instruction, operands are left unresolved
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    // `None` for bytes that don't decode to a known opcode
+    pub name: Option<&'static str>,
+    pub mode: Mode,
+    pub operand: u16,
+}
+
+impl Line {
+    // the absolute address a branch or jump goes to
+    pub fn target(&self) -> Option<u16> {
+        match (self.name, self.mode) {
+            (Some(_), Mode::Relative) => Some(self.operand),
+            (Some("JMP"), Mode::Absolute) | (Some("JSR"), Mode::Absolute) => Some(self.operand),
+            _ => None,
+        }
+    }
+
+    pub fn operand_text(&self) -> String {
+        let value = self.operand;
+
+        match self.mode {
+            Mode::Implied => String::new(),
+            Mode::Accumulator => "A".to_string(),
+            Mode::Immediate => format!("#${:02X}", value),
+            Mode::ZeroPage => format!("${:02X}", value),
+            Mode::ZeroPageX => format!("${:02X},X", value),
+            Mode::ZeroPageY => format!("${:02X},Y", value),
+            Mode::Relative | Mode::Absolute => format!("${:04X}", value),
+            Mode::AbsoluteX => format!("${:04X},X", value),
+            Mode::AbsoluteY => format!("${:04X},Y", value),
+            Mode::Indirect => format!("(${:04X})", value),
+            Mode::IndirectX => format!("(${:02X},X)", value),
+            Mode::IndirectY => format!("(${:02X}),Y", value),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self.name {
+            Some(name) => format!("{} {}", name, self.operand_text()).trim_end().to_string(),
+            None => format!(".db ${:02X}", self.bytes[0]),
+        }
+    }
+}
+
+// decodes the instruction at `addr`, reading through `read`
+pub fn decode<F: Fn(u16) -> u8>(read: F, addr: u16) -> Line {
+    let opcode = read(addr);
+
+    let (name, mode) = match opcodes::get(opcode as usize) {
+        Some((name, mode, _)) => (Some(name), mode),
+        None => (None, Mode::Implied),
+    };
+
+    let bytes: Vec<u8> = (0..mode.size() as u16)
+        .map(|i| read(addr.wrapping_add(i)))
+        .collect();
+
+    let operand = match bytes.len() {
+        2 if mode == Mode::Relative => {
+            // relative to the instruction that follows the branch
+            addr.wrapping_add(2).wrapping_add(bytes[1] as i8 as u16)
+        },
+        2 => bytes[1] as u16,
+        3 => (bytes[1] as u16) | ((bytes[2] as u16) << 8),
+        _ => 0,
+    };
+
+    Line { addr, bytes, name, mode, operand }
+}
+
+// disassembles `bytes` as if they were mapped at `origin`
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Line> {
+    let end = origin as usize + bytes.len();
+
+    // operands running off the end read as zero
+    let read = |addr: u16| {
+        let offset = (addr as usize).wrapping_sub(origin as usize);
+        bytes.get(offset).copied().unwrap_or(0)
+    };
+
+    let mut lines = vec![];
+    let mut addr = origin as usize;
+
+    while addr < end {
+        let line = decode(read, addr as u16);
+
+        addr += line.bytes.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+// disassembles `start..=end` of the cpu address space
+pub fn disassemble_bus<B: CpuBus>(bus: &B, start: u16, end: u16) -> Vec<Line> {
+    let bytes: Vec<u8> = (start..=end).map(|addr| bus.peek(addr as usize)).collect();
+    disassemble(&bytes, start)
+}
+
+pub fn label(addr: u16) -> String {
+    format!("L_{:04X}", addr)
+}
+
+// renders a listing, naming every branch or jump target that lands on
+// the start of a disassembled instruction
+pub fn to_text(lines: &[Line]) -> String {
+    let starts: BTreeSet<u16> = lines.iter().map(|line| line.addr).collect();
+    let labels: BTreeSet<u16> = lines.iter()
+        .filter_map(|line| line.target())
+        .filter(|target| starts.contains(target))
+        .collect();
+
+    let mut text = String::new();
+
+    for line in lines {
+        if labels.contains(&line.addr) {
+            text.push_str(&format!("{}:\n", label(line.addr)));
+        }
+
+        let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+        let instruction = match line.target() {
+            Some(target) if labels.contains(&target) => {
+                format!("{} {}", line.name.unwrap_or_default(), label(target))
+            },
+            _ => line.text(),
+        };
+
+        text.push_str(&format!("{:04X}  {:8}  {}\n", line.addr, bytes.join(" "), instruction));
+    }
+
+    text
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+// small c-like expressions for debugger conditions, e.g.
+// `a == $10 && [$0300] != 0` or `x > 5 || p & $80`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr(Node);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Var {
+    A,
+    X,
+    Y,
+    P,
+    Sp,
+    Pc,
+    Scanline,
+    Dot,
+    Cycles,
+    // address and byte of the access that hit a watchpoint
+    Addr,
+    Value,
+}
+
+pub trait Context {
+    fn var(&self, var: Var) -> i64;
+    fn memory(&self, addr: u16) -> u8;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExprError(pub String);
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad condition: {}", self.0)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl From<ExprError> for JsValue {
+    fn from(error: ExprError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(i64),
+    Var(Var),
+    Memory(Box<Node>),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const SYMBOLS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=",
+    "<", ">", "|", "^", "&", "+", "-", "!", "(", ")", "[", "]",
+];
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, ExprError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let node = parser.expr(0)?;
+
+        match parser.tokens.get(parser.pos) {
+            Some(token) => Err(ExprError(format!("unexpected {:?}", token))),
+            None => Ok(Expr(node)),
+        }
+    }
+
+    pub fn eval(&self, ctx: &dyn Context) -> i64 {
+        eval(&self.0, ctx)
+    }
+
+    pub fn test(&self, ctx: &dyn Context) -> bool {
+        self.eval(ctx) != 0
+    }
+}
+
+fn eval(node: &Node, ctx: &dyn Context) -> i64 {
+    match node {
+        Node::Number(value) => *value,
+        Node::Var(var) => ctx.var(*var),
+        Node::Memory(addr) => ctx.memory(eval(addr, ctx) as u16) as i64,
+        Node::Not(node) => (eval(node, ctx) == 0) as i64,
+        Node::Neg(node) => eval(node, ctx).wrapping_neg(),
+        Node::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, ctx);
+
+            // short circuit so `[addr]` on the right isn't read needlessly
+            match op {
+                Op::Or if lhs != 0 => return 1,
+                Op::And if lhs == 0 => return 0,
+                _ => {},
+            }
+
+            let rhs = eval(rhs, ctx);
+
+            match op {
+                Op::Or | Op::And => (rhs != 0) as i64,
+                Op::Eq => (lhs == rhs) as i64,
+                Op::Ne => (lhs != rhs) as i64,
+                Op::Lt => (lhs < rhs) as i64,
+                Op::Le => (lhs <= rhs) as i64,
+                Op::Gt => (lhs > rhs) as i64,
+                Op::Ge => (lhs >= rhs) as i64,
+                Op::BitOr => lhs | rhs,
+                Op::BitXor => lhs ^ rhs,
+                Op::BitAnd => lhs & rhs,
+                Op::Add => lhs.wrapping_add(rhs),
+                Op::Sub => lhs.wrapping_sub(rhs),
+            }
+        },
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let (token, len) = if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            (Token::Op(symbol), symbol.len())
+        } else {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '$' || c == '_'))
+                .unwrap_or(rest.len());
+
+            if len == 0 {
+                // not sliced, the character can be more than a byte
+                let c = rest.chars().next().unwrap_or_default();
+                return Err(ExprError(format!("unexpected `{}`", c)));
+            }
+
+            let word = &rest[..len];
+
+            let number = if let Some(hex) = word.strip_prefix('$').or_else(|| word.strip_prefix("0x")) {
+                Some(i64::from_str_radix(hex, 16))
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                Some(word.parse())
+            } else {
+                None
+            };
+
+            match number {
+                Some(Ok(value)) => (Token::Number(value), len),
+                Some(Err(_)) => return Err(ExprError(format!("bad number `{}`", word))),
+                None => (Token::Ident(word.to_ascii_lowercase()), len),
+            }
+        };
+
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ExprError> {
+        match self.next() {
+            Some(Token::Op(op)) if op == symbol => Ok(()),
+            _ => Err(ExprError(format!("expected `{}`", symbol))),
+        }
+    }
+
+    // precedence climbing, higher binds tighter
+    fn expr(&mut self, min: u8) -> Result<Node, ExprError> {
+        let mut lhs = self.unary()?;
+
+        while let Some(Token::Op(symbol)) = self.tokens.get(self.pos) {
+            let (op, precedence) = match *symbol {
+                "||" => (Op::Or, 1),
+                "&&" => (Op::And, 2),
+                "==" => (Op::Eq, 3),
+                "!=" => (Op::Ne, 3),
+                "<" => (Op::Lt, 4),
+                "<=" => (Op::Le, 4),
+                ">" => (Op::Gt, 4),
+                ">=" => (Op::Ge, 4),
+                "|" => (Op::BitOr, 5),
+                "^" => (Op::BitXor, 6),
+                "&" => (Op::BitAnd, 7),
+                "+" => (Op::Add, 8),
+                "-" => (Op::Sub, 8),
+                _ => break,
+            };
+
+            if precedence < min {
+                break;
+            }
+
+            self.pos += 1;
+
+            let rhs = self.expr(precedence + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Ident(name)) => {
+                let var = match name.as_str() {
+                    "a" => Var::A,
+                    "x" => Var::X,
+                    "y" => Var::Y,
+                    "p" => Var::P,
+                    "sp" | "s" => Var::Sp,
+                    "pc" => Var::Pc,
+                    "scanline" => Var::Scanline,
+                    "dot" => Var::Dot,
+                    "cycles" => Var::Cycles,
+                    "addr" => Var::Addr,
+                    "value" => Var::Value,
+                    _ => return Err(ExprError(format!("unknown name `{}`", name))),
+                };
+
+                Ok(Node::Var(var))
+            },
+            Some(Token::Op("!")) => Ok(Node::Not(Box::new(self.unary()?))),
+            Some(Token::Op("-")) => Ok(Node::Neg(Box::new(self.unary()?))),
+            Some(Token::Op("(")) => {
+                let node = self.expr(0)?;
+                self.expect(")")?;
+                Ok(node)
+            },
+            Some(Token::Op("[")) => {
+                let node = self.expr(0)?;
+                self.expect("]")?;
+                Ok(Node::Memory(Box::new(node)))
+            },
+            Some(token) => Err(ExprError(format!("unexpected {:?}", token))),
+            None => Err(ExprError("unexpected end".to_string())),
+        }
+    }
+}
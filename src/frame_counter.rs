@@ -0,0 +1,70 @@
+use super::state::{Snapshot, StateReader, StateWriter, StateError};
+
+pub const FRAME_COUNTER: usize = 0x4017;
+
+const MODE_FIVE_STEP: u8 = 0b10000000;
+const IRQ_INHIBIT: u8 = 0b01000000;
+
+// the apu's frame sequencer, $4017. there are no sound channels for it to
+// clock yet, but the interrupt at the end of the 4 step sequence is what
+// games time things against. its steps run as scheduler events
+#[derive(Clone)]
+pub struct FrameCounter {
+    pub five_step: bool,
+    pub irq_inhibit: bool,
+    // the step the next event runs
+    pub step: usize,
+    // set by writes, the sequence starts over from the first step
+    pub restart: bool,
+}
+
+impl FrameCounter {
+    pub fn new() -> FrameCounter {
+        FrameCounter {
+            five_step: false,
+            irq_inhibit: false,
+            step: 0,
+            restart: true,
+        }
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.five_step = value & MODE_FIVE_STEP != 0;
+        self.irq_inhibit = value & IRQ_INHIBIT != 0;
+        self.restart = true;
+    }
+
+    // whether the step about to run raises the interrupt
+    pub fn irq(&self) -> bool {
+        self.step == 3 && !self.five_step && !self.irq_inhibit
+    }
+}
+
+impl Default for FrameCounter {
+    fn default() -> FrameCounter {
+        FrameCounter::new()
+    }
+}
+
+impl Snapshot for FrameCounter {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_bool(self.five_step);
+        w.write_bool(self.irq_inhibit);
+        w.write_u8(self.step as u8);
+        w.write_bool(self.restart);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.five_step = r.read_bool()?;
+        self.irq_inhibit = r.read_bool()?;
+        self.step = r.read_u8()? as usize;
+        self.restart = r.read_bool()?;
+
+        // there are 4 steps in either mode
+        if self.step > 3 {
+            return Err(StateError::Invalid("frame counter step"));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,445 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use super::{
+    Nes,
+    bus::CpuBus,
+    debugger::BreakReason,
+    inspect::MemorySpace,
+};
+
+// gdb has no 6502 target of its own, so the layout is described to it:
+// a, x, y, p and sp are 8 bits, pc is 16 bits, little endian
+const TARGET_XML: &str = concat!(
+    r#"<?xml version="1.0"?>"#,
+    r#"<!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+    r#"<target version="1.0">"#,
+    r#"<feature name="org.nes.6502.core">"#,
+    r#"<reg name="a" bitsize="8" type="uint8" regnum="0"/>"#,
+    r#"<reg name="x" bitsize="8" type="uint8"/>"#,
+    r#"<reg name="y" bitsize="8" type="uint8"/>"#,
+    r#"<reg name="p" bitsize="8" type="uint8"/>"#,
+    r#"<reg name="sp" bitsize="8" type="uint8"/>"#,
+    r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#,
+    r#"</feature>"#,
+    r#"</target>"#,
+);
+
+// advertised in qSupported, in characters of packet data
+const PACKET_SIZE: usize = 0x1000;
+
+const REGISTERS: usize = 6;
+const REG_PC: usize = 5;
+
+// signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// ^c from the client while the target runs
+const INTERRUPT: u8 = 0x03;
+
+// a connection the stub can talk over
+pub trait Transport: Read + Write {
+    // whether the client asked to stop a running target, mustn't block
+    fn interrupted(&mut self) -> bool {
+        false
+    }
+}
+
+impl Transport for TcpStream {
+    fn interrupted(&mut self) -> bool {
+        let mut byte = [0];
+
+        if self.set_nonblocking(true).is_err() {
+            return false;
+        }
+
+        let interrupted = matches!(self.peek(&mut byte), Ok(1) if byte[0] == INTERRUPT);
+
+        if interrupted {
+            let _ = self.read(&mut byte);
+        }
+
+        let _ = self.set_nonblocking(false);
+        interrupted
+    }
+}
+
+pub struct GdbStub<T: Transport> {
+    transport: T,
+    // the last stop was the client's ^c rather than the debugger's
+    interrupted: bool,
+    // what the client inserted, taken out again when it detaches
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<(u16, u16, bool, bool)>,
+}
+
+// waits for a single client on `addr`, then serves it until it detaches
+pub fn serve<A: ToSocketAddrs>(nes: &mut Nes, addr: A) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+
+    stream.set_nodelay(true)?;
+    GdbStub::new(stream).run(nes)
+}
+
+impl<T: Transport> GdbStub<T> {
+    pub fn new(transport: T) -> GdbStub<T> {
+        GdbStub {
+            transport,
+            interrupted: false,
+            breakpoints: vec![],
+            watchpoints: vec![],
+        }
+    }
+
+    // the target stays stopped except while the client has it running
+    pub fn run(&mut self, nes: &mut Nes) -> io::Result<()> {
+        nes.pause();
+
+        while let Some(packet) = self.read_packet()? {
+            match self.handle(nes, &packet) {
+                Some(reply) => self.write_packet(&reply)?,
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    // `None` once the session is over
+    fn handle(&mut self, nes: &mut Nes, packet: &str) -> Option<String> {
+        // every command is a single ascii character
+        let (command, args) = match packet.get(..1) {
+            Some(command) => (command, &packet[1..]),
+            None => return Some(String::new()),
+        };
+
+        let reply = match command {
+            "?" => self.stop_reply(nes),
+            "g" => registers(nes).iter()
+                .enumerate()
+                .map(|(i, value)| hex_le(*value, register_size(i)))
+                .collect(),
+            "G" => {
+                let bytes = decode_hex(args).unwrap_or_default();
+
+                if bytes.len() != (0..REGISTERS).map(register_size).sum::<usize>() {
+                    return Some("E01".to_string());
+                }
+
+                let pc = u16::from_le_bytes([bytes[5], bytes[6]]);
+
+                for (i, value) in bytes[..5].iter().enumerate() {
+                    set_register(nes, i, *value as u16);
+                }
+
+                set_register(nes, REG_PC, pc);
+                "OK".to_string()
+            },
+            "p" => match usize::from_str_radix(args, 16).ok().filter(|i| *i < REGISTERS) {
+                Some(i) => hex_le(registers(nes)[i], register_size(i)),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(i, value)| {
+                    let i = usize::from_str_radix(i, 16).ok().filter(|i| *i < REGISTERS)?;
+                    let bytes = decode_hex(value)?;
+
+                    Some((i, bytes.iter().rev().fold(0u16, |acc, byte| (acc << 8) | *byte as u16)))
+                });
+
+                match parsed {
+                    Some((i, value)) => {
+                        set_register(nes, i, value);
+                        "OK".to_string()
+                    },
+                    None => "E01".to_string(),
+                }
+            },
+            "m" => match parse_range(args) {
+                // two characters a byte have to fit in a packet
+                Some((_, len)) if len > PACKET_SIZE / 2 => "E01".to_string(),
+                Some((addr, len)) => {
+                    let bus = nes.bus();
+
+                    (0..len)
+                        .map(|i| format!("{:02x}", bus.peek(addr.wrapping_add(i) & 0xffff)))
+                        .collect()
+                },
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| {
+                    Some((parse_range(range)?, decode_hex(data)?))
+                });
+
+                match parsed {
+                    Some(((addr, len), data)) if data.len() == len => {
+                        // registers and open bus don't hold what's poked into them
+                        let written = data.iter().enumerate().fold(true, |written, (i, value)| {
+                            nes.poke(MemorySpace::CpuBus, addr.wrapping_add(i) & 0xffff, *value) && written
+                        });
+
+                        if written { "OK" } else { "E01" }.to_string()
+                    },
+                    _ => "E01".to_string(),
+                }
+            },
+            "c" => {
+                nes.resume();
+                self.run_until_stopped(nes);
+                self.stop_reply(nes)
+            },
+            "s" => {
+                nes.step_into();
+                self.run_until_stopped(nes);
+                self.stop_reply(nes)
+            },
+            "Z" | "z" => self.breakpoint(nes, command == "Z", args),
+            "D" => {
+                self.release(nes);
+                self.write_packet("OK").ok()?;
+                return None;
+            },
+            // kill gets no reply, the emulator carries on as after a detach
+            "k" => {
+                self.release(nes);
+                return None;
+            },
+            "H" => "OK".to_string(),
+            "q" => query(args),
+            // anything else is unsupported, which gdb expects as empty
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    // takes out what the client inserted and lets the emulator run again
+    fn release(&mut self, nes: &mut Nes) {
+        for addr in self.breakpoints.drain(..) {
+            nes.remove_breakpoint(addr);
+        }
+
+        for (start, end, read, write) in self.watchpoints.drain(..) {
+            nes.remove_watchpoint(start, end, read, write);
+        }
+
+        nes.resume();
+    }
+
+    fn run_until_stopped(&mut self, nes: &mut Nes) {
+        self.interrupted = false;
+
+        while !nes.paused() {
+            nes.tick_frame();
+
+            if self.transport.interrupted() {
+                self.interrupted = true;
+                nes.pause();
+            }
+        }
+    }
+
+    // Z0/Z1 are execute breakpoints, Z2/Z3/Z4 write/read/access watchpoints
+    fn breakpoint(&mut self, nes: &mut Nes, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+
+        let kind = parts.next();
+        let addr = parts.next().and_then(|addr| u16::from_str_radix(addr, 16).ok());
+        let len = parts.next().and_then(|len| u16::from_str_radix(len, 16).ok()).unwrap_or(1);
+
+        let addr = match addr {
+            Some(addr) => addr,
+            None => return "E01".to_string(),
+        };
+
+        let end = addr.saturating_add(len.max(1) - 1);
+
+        let (read, write) = match kind {
+            Some("0") | Some("1") => {
+                if insert {
+                    let _ = nes.add_breakpoint(addr, None);
+                    self.breakpoints.push(addr);
+                } else {
+                    nes.remove_breakpoint(addr);
+                    self.breakpoints.retain(|inserted| *inserted != addr);
+                }
+
+                return "OK".to_string();
+            },
+            Some("2") => (false, true),
+            Some("3") => (true, false),
+            Some("4") => (true, true),
+            _ => return String::new(),
+        };
+
+        let watchpoint = (addr, end, read, write);
+
+        if insert {
+            let _ = nes.add_watchpoint(addr, end, read, write, None);
+            self.watchpoints.push(watchpoint);
+        } else {
+            nes.remove_watchpoint(addr, end, read, write);
+            self.watchpoints.retain(|inserted| *inserted != watchpoint);
+        }
+
+        "OK".to_string()
+    }
+
+    fn stop_reply(&self, nes: &Nes) -> String {
+        match &nes.debugger().reason {
+            Some(BreakReason::Watchpoint { addr, write, .. }) => {
+                // an access watchpoint stops on either, and gdb wants to
+                // hear it was that kind
+                let access = self.watchpoints.iter().any(|(start, end, reads, writes)| {
+                    *reads && *writes && (*start..=*end).contains(addr)
+                });
+
+                let kind = match (access, *write) {
+                    (true, _) => "awatch",
+                    (false, true) => "watch",
+                    (false, false) => "rwatch",
+                };
+
+                format!("T{:02x}{}:{:04x};", SIGTRAP, kind, addr)
+            },
+            Some(BreakReason::Pause) if self.interrupted => format!("S{:02x}", SIGINT),
+            Some(BreakReason::UnknownOpcode { .. }) => format!("S{:02x}", SIGILL),
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    // reads one packet, acking it, `None` when the client hung up
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // skip acks and anything else between packets
+            match self.read_byte()? {
+                Some(b'$') => {},
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = vec![];
+
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let mut checksum = [0; 2];
+            self.transport.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum).ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+            if expected != Some(sum(&data)) {
+                self.transport.write_all(b"-")?;
+                continue;
+            }
+
+            self.transport.write_all(b"+")?;
+
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+
+        match self.transport.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, sum(data.as_bytes()));
+
+        self.transport.write_all(packet.as_bytes())?;
+        self.transport.flush()
+    }
+}
+
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        return format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE);
+    }
+
+    if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        return match parse_range(range) {
+            Some((offset, len)) => {
+                let rest = TARGET_XML.get(offset.min(TARGET_XML.len())..).unwrap_or_default();
+
+                if rest.len() > len {
+                    format!("m{}", &rest[..len])
+                } else {
+                    format!("l{}", rest)
+                }
+            },
+            None => "E01".to_string(),
+        };
+    }
+
+    match args {
+        "Attached" => "1".to_string(),
+        "C" => "QC1".to_string(),
+        "fThreadInfo" => "m1".to_string(),
+        "sThreadInfo" => "l".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn registers(nes: &Nes) -> [u16; REGISTERS] {
+    let cpu = nes.cpu();
+
+    [cpu.a as u16, cpu.x as u16, cpu.y as u16, cpu.p as u16, cpu.sp as u16, cpu.pc]
+}
+
+fn set_register(nes: &mut Nes, i: usize, value: u16) {
+    let cpu = &mut nes.machine_mut().cpu;
+
+    match i {
+        0 => cpu.a = value as u8,
+        1 => cpu.x = value as u8,
+        2 => cpu.y = value as u8,
+        3 => cpu.p = value as u8,
+        4 => cpu.sp = value as u8,
+        _ => cpu.pc = value,
+    }
+}
+
+// in bytes, only pc is wider than one
+fn register_size(i: usize) -> usize {
+    if i == REG_PC { 2 } else { 1 }
+}
+
+fn hex_le(value: u16, bytes: usize) -> String {
+    value.to_le_bytes()[..bytes].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// `addr,len`, both hex
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+
+    Some((usize::from_str_radix(addr, 16).ok()?, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn sum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+use super::{Nes, input::PLAYERS, movie};
+
+// buttons for a player from a frame on, until their next press
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Press {
+    pub frame: u64,
+    pub player: usize,
+    pub buttons: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "input script line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// input for runs without anyone at the controls, one press a line as
+// `<frame> <player> <buttons>`. frames count from 0, players from 1 and
+// buttons are an fm2 gamepad field, e.g. `....T...` for start. blank
+// lines and lines starting with `#` are skipped
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputScript {
+    // in frame order, presses on the same frame in the order written
+    pub presses: Vec<Press>,
+}
+
+impl InputScript {
+    pub fn new() -> InputScript {
+        InputScript::default()
+    }
+
+    pub fn parse(text: &str) -> Result<InputScript, ScriptError> {
+        let mut presses = vec![];
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: String| ScriptError { line: i + 1, reason };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            if fields.len() != 3 {
+                return Err(error("expected `<frame> <player> <buttons>`".to_string()));
+            }
+
+            let frame = fields[0].parse().map_err(|_| error(format!("bad frame `{}`", fields[0])))?;
+
+            let player = match fields[1].parse::<usize>() {
+                Ok(player) if (1..=PLAYERS).contains(&player) => player - 1,
+                _ => return Err(error(format!("bad player `{}`", fields[1]))),
+            };
+
+            let buttons = movie::parse_buttons(fields[2]).map_err(error)?;
+
+            presses.push(Press { frame, player, buttons });
+        }
+
+        // stable, so the last press written for a frame wins
+        presses.sort_by_key(|press| press.frame);
+
+        Ok(InputScript { presses })
+    }
+
+    // sets the buttons pressed on `frame`, call it before running the frame
+    pub fn apply(&self, nes: &mut Nes, frame: u64) {
+        let start = self.presses.partition_point(|press| press.frame < frame);
+
+        for press in self.presses[start..].iter().take_while(|press| press.frame == frame) {
+            nes.set_buttons(press.player, press.buttons);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use wasm_bindgen::prelude::*;
+use super::{
+    bus::{BusRead, BusWrite},
+    state::{Snapshot, StateReader, StateWriter, StateError},
+};
+
+pub const PLAYERS: usize = 4;
+
+pub const BUTTON_A: u8 = 0b00000001;
+pub const BUTTON_B: u8 = 0b00000010;
+pub const BUTTON_SELECT: u8 = 0b00000100;
+pub const BUTTON_START: u8 = 0b00001000;
+pub const BUTTON_UP: u8 = 0b00010000;
+pub const BUTTON_DOWN: u8 = 0b00100000;
+pub const BUTTON_LEFT: u8 = 0b01000000;
+pub const BUTTON_RIGHT: u8 = 0b10000000;
+
+// id bits returned by reads 17-24 of a four score
+const FOUR_SCORE_SIGNATURE: [u8; 2] = [0b00001000, 0b00000100];
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputMode {
+    // two controllers plugged straight into the ports
+    Standard,
+    // nes four score, players 3/4 follow players 1/2 on d0
+    FourScore,
+    // famicom expansion port, players 3/4 are read on d1
+    Famicom,
+}
+
+#[derive(Clone)]
+pub struct Input {
+    pub mode: InputMode,
+    pub buttons: [u8; PLAYERS],
+    pub strobe: bool,
+
+    // shift registers for d0 and d1 of each port
+    shifters: [[u32; 2]; 2],
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            mode: InputMode::Standard,
+            buttons: [0; PLAYERS],
+            strobe: false,
+            shifters: [[0; 2]; 2],
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: InputMode) {
+        self.mode = mode;
+        self.latch();
+    }
+
+    // what the d0/d1 shift registers of `port` get loaded with
+    fn latched(&self, port: usize) -> [u32; 2] {
+        let first = self.buttons[port] as u32;
+        let second = self.buttons[port + 2] as u32;
+
+        // unused bits read back as 1 once a register is empty
+        match self.mode {
+            InputMode::Standard => [0xffffff00 | first, 0xffffffff],
+            InputMode::FourScore => {
+                let signature = FOUR_SCORE_SIGNATURE[port] as u32;
+
+                [0xff000000 | (signature << 16) | (second << 8) | first, 0xffffffff]
+            },
+            InputMode::Famicom => [0xffffff00 | first, 0xffffff00 | second],
+        }
+    }
+
+    fn latch(&mut self) {
+        for port in 0..self.shifters.len() {
+            self.shifters[port] = self.latched(port);
+        }
+    }
+
+    // the value the next read of `port` returns, without shifting
+    fn peek_port(&self, port: usize) -> u8 {
+        let bits = if self.strobe {
+            self.latched(port)
+        } else {
+            self.shifters[port]
+        };
+
+        let value = (bits[0] & 1) as u8 | (((bits[1] & 1) as u8) << 1);
+
+        if self.mode != InputMode::Famicom {
+            value & 1
+        } else {
+            value
+        }
+    }
+
+    fn shift(&mut self, port: usize) -> u8 {
+        // controllers keep reloading while strobe is held high
+        if self.strobe {
+            self.latch();
+        }
+
+        let mut value = 0;
+
+        for (bit, shifter) in self.shifters[port].iter_mut().enumerate() {
+            value |= ((*shifter & 1) as u8) << bit;
+            *shifter = (*shifter >> 1) | 0x80000000;
+        }
+
+        // only d0 is wired up on a standard or four score port
+        if self.mode != InputMode::Famicom {
+            value &= 1;
+        }
+
+        value
+    }
+}
+
+impl Default for Input {
+    fn default() -> Input {
+        Input::new()
+    }
+}
+
+impl BusRead for Input {
+    fn read(&mut self, addr: usize) -> Option<u8> {
+        match addr {
+            0x4016 => Some(self.shift(0)),
+            0x4017 => Some(self.shift(1)),
+            _ => None,
+        }
+    }
+
+    fn peek(&self, addr: usize) -> Option<u8> {
+        match addr {
+            0x4016 => Some(self.peek_port(0)),
+            0x4017 => Some(self.peek_port(1)),
+            _ => None,
+        }
+    }
+}
+
+impl BusWrite for Input {
+    fn write(&mut self, addr: usize, value: u8) -> bool {
+        if addr == 0x4016 {
+            let strobe = value & 1 != 0;
+
+            // registers keep reloading until strobe goes low
+            if self.strobe || strobe {
+                self.latch();
+            }
+
+            self.strobe = strobe;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Snapshot for Input {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_u8(self.mode as u8);
+        w.write_bytes(&self.buttons);
+        w.write_bool(self.strobe);
+
+        for shifter in self.shifters.iter().flatten() {
+            w.write_u32(*shifter);
+        }
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.mode = match r.read_u8()? {
+            0 => InputMode::Standard,
+            1 => InputMode::FourScore,
+            2 => InputMode::Famicom,
+            _ => return Err(StateError::Invalid("input mode")),
+        };
+
+        r.read_into(&mut self.buttons)?;
+        self.strobe = r.read_bool()?;
+
+        for shifter in self.shifters.iter_mut().flatten() {
+            *shifter = r.read_u32()?;
+        }
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,149 @@
+use wasm_bindgen::prelude::*;
+use super::bus::{Bus, CpuBus};
+
+// bytes per line of a hex dump
+pub const HEX_DUMP_WIDTH: usize = 16;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemorySpace {
+    // the whole 64k as the cpu sees it
+    CpuBus,
+    // the 2k of internal ram, unmirrored
+    Ram,
+    PrgRom,
+    PrgRam,
+    // pattern tables, rom or ram
+    Chr,
+    // the 2k of nametable ram inside the console
+    Ciram,
+    // 32 bytes, the mirrored backdrop entries included as stored
+    Palette,
+    Oam,
+}
+
+impl Bus {
+    // bytes addressable in `space`, 0 for cartridge spaces with no cartridge
+    pub fn memory_size(&self, space: MemorySpace) -> usize {
+        let cartridge = self.cartridge.as_ref();
+
+        match space {
+            MemorySpace::CpuBus => 0x10000,
+            MemorySpace::Ram => self.ram.data.len(),
+            MemorySpace::PrgRom => cartridge.map_or(0, |cartridge| cartridge.prg_rom.len()),
+            MemorySpace::PrgRam => cartridge.map_or(0, |cartridge| cartridge.prg_ram.len()),
+            MemorySpace::Chr => cartridge.map_or(0, |cartridge| cartridge.chr.len()),
+            MemorySpace::Ciram => self.ppu.ciram.len(),
+            MemorySpace::Palette => self.ppu.palette.len(),
+            MemorySpace::Oam => self.ppu.oam.len(),
+        }
+    }
+
+    // reads `addr` of `space` without side effects, `None` past its end
+    pub fn peek_memory(&self, space: MemorySpace, addr: usize) -> Option<u8> {
+        if addr >= self.memory_size(space) {
+            return None;
+        }
+
+        let value = match space {
+            MemorySpace::CpuBus => self.peek(addr),
+            MemorySpace::Ram => self.ram.data[addr],
+            MemorySpace::Ciram => self.ppu.ciram[addr],
+            MemorySpace::Palette => self.ppu.palette[addr],
+            MemorySpace::Oam => self.ppu.oam[addr],
+            _ => {
+                let cartridge = self.cartridge.as_ref()?;
+
+                match space {
+                    MemorySpace::PrgRom => cartridge.prg_rom[addr],
+                    MemorySpace::PrgRam => cartridge.prg_ram[addr],
+                    _ => cartridge.chr[addr],
+                }
+            },
+        };
+
+        Some(value)
+    }
+
+    // writes straight into the backing memory, rom included, returning
+    // false when nothing is there. on the cpu bus only memory can be
+    // poked, registers are left alone
+    pub fn poke_memory(&mut self, space: MemorySpace, addr: usize, value: u8) -> bool {
+        if addr >= self.memory_size(space) {
+            return false;
+        }
+
+        match space {
+            MemorySpace::CpuBus => {
+                let prg_rom_size = self.memory_size(MemorySpace::PrgRom);
+
+                match addr {
+                    0x0000..=0x1fff => self.poke_memory(MemorySpace::Ram, addr & 0x07ff, value),
+                    0x6000..=0x7fff => self.poke_memory(MemorySpace::PrgRam, addr - 0x6000, value),
+                    0x8000..=0xffff if prg_rom_size > 0 => {
+                        self.poke_memory(MemorySpace::PrgRom, (addr - 0x8000) % prg_rom_size, value)
+                    },
+                    _ => false,
+                }
+            },
+            MemorySpace::Ram => {
+                self.ram.data[addr] = value;
+                true
+            },
+            MemorySpace::Ciram => {
+                self.ppu.ciram[addr] = value;
+                true
+            },
+            MemorySpace::Palette => {
+                self.ppu.palette[addr] = value;
+                true
+            },
+            MemorySpace::Oam => {
+                self.ppu.oam[addr] = value;
+                true
+            },
+            _ => match &mut self.cartridge {
+                Some(cartridge) => {
+                    match space {
+                        MemorySpace::PrgRom => cartridge.prg_rom[addr] = value,
+                        MemorySpace::PrgRam => cartridge.prg_ram[addr] = value,
+                        _ => cartridge.chr[addr] = value,
+                    }
+
+                    true
+                },
+                None => false,
+            },
+        }
+    }
+
+    // `len` bytes from `start`, stopping at the end of the space
+    pub fn peek_memory_range(&self, space: MemorySpace, start: usize, len: usize) -> Vec<u8> {
+        let end = start.saturating_add(len).min(self.memory_size(space));
+
+        (start..end).filter_map(|addr| self.peek_memory(space, addr)).collect()
+    }
+}
+
+// `0010: 41 42 43 ...  |ABC...|`, with `origin` as the first address
+pub fn hex_dump(bytes: &[u8], origin: usize) -> String {
+    let mut text = String::new();
+
+    for (i, line) in bytes.chunks(HEX_DUMP_WIDTH).enumerate() {
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+        let ascii: String = line.iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+
+        text.push_str(&format!(
+            "{:04X}: {:width$}  |{}|\n",
+            origin + i * HEX_DUMP_WIDTH,
+            hex.join(" "),
+            ascii,
+            width = HEX_DUMP_WIDTH * 3 - 1,
+        ));
+    }
+
+    text
+}
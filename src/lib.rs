@@ -2,15 +2,54 @@ extern crate wasm_bindgen;
 
 pub mod bus;
 pub mod cpu;
+pub mod machine;
+pub mod scheduler;
+pub mod opcodes;
+pub mod disasm;
+pub mod debugger;
+pub mod expr;
 pub mod ppu;
+pub mod region;
+pub mod palette;
+pub mod viewer;
+pub mod inspect;
+pub mod png;
 pub mod memory;
+pub mod input;
+pub mod frame_counter;
+pub mod audio;
+pub mod wav;
+pub mod state;
+pub mod checksum;
+pub mod rewind;
+pub mod cartridge;
+pub mod movie;
+pub mod trace;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sram;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod testrom;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gdb;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
 
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::{JsCast, JsValue};
-use console_error_panic_hook;
-use web_sys::console;
-
-pub const CYCLES_PER_FRAME: u64 = 29781;
+use bus::{Bus, CpuBus};
+use cpu::Cpu;
+use machine::{Machine, PpuSync};
+use input::InputMode;
+use region::Region;
+use inspect::MemorySpace;
+use state::{Snapshot, StateReader, StateWriter, StateError};
+use rewind::Rewind;
+use cartridge::{Cartridge, CartridgeError};
+use movie::{Movie, MovieError, Playback};
+use trace::{TraceBuffer, TraceEntry, TraceSink};
+use audio::AudioCapture;
+use wav::SampleFormat;
+use debugger::{BreakReason, Debugger, Watchpoint};
+use expr::{Expr, ExprError};
 
 pub trait Tick {
     fn tick(&mut self);
@@ -18,8 +57,16 @@ pub trait Tick {
 
 #[wasm_bindgen]
 pub struct Nes {
-    remaining_cycles: u64,
-    bus: bus::Bus,
+    // the ppu frame the one being run ends on, `None` between frames
+    frame_end: Option<u64>,
+    frame: u64,
+    machine: Machine,
+    rewind: Option<Rewind>,
+    movie: Option<Playback>,
+    tracer: Option<Box<dyn TraceSink>>,
+    trace_buffer: TraceBuffer,
+    debugger: Debugger,
+    audio: Option<AudioCapture>,
 }
 
 #[wasm_bindgen]
@@ -29,48 +76,668 @@ impl Nes {
         console_error_panic_hook::set_once();
 
         Nes {
-            remaining_cycles: 0,
-            bus: bus::Bus::new(),
+            frame_end: None,
+            frame: 0,
+            machine: Machine::new(),
+            rewind: None,
+            movie: None,
+            tracer: None,
+            trace_buffer: TraceBuffer::default(),
+            debugger: Debugger::new(),
+            audio: None,
         }
     }
 
+    // the header's region wins over `set_region` when it has one
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        let cartridge = Cartridge::new(rom)?;
+
+        if let Some(region) = cartridge.region {
+            self.machine.set_region(region);
+        }
+
+        self.machine.bus.insert(cartridge);
+        self.reset();
+
+        // snapshots of the previous game can't be loaded anymore
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+
+        Ok(())
+    }
+
     pub fn reset(&mut self) {
+        self.record_command(movie::COMMAND_SOFT_RESET);
+
         // reset cpu
-        self.bus.cpu.borrow_mut().reset();
+        self.machine.reset();
     }
 
+    pub fn power_on(&mut self) {
+        self.record_command(movie::COMMAND_HARD_RESET);
+
+        self.machine.power_on();
+        self.frame_end = None;
+        self.frame = 0;
+
+        self.machine.reset();
+    }
+
+    // runs a single cpu cycle, returning the trace line of the instruction
+    // started on it (empty when the cpu is still busy)
     pub fn tick_cpu(&mut self) -> String {
-        let mut cpu = self.bus.cpu.borrow_mut();
-        cpu.tick();
-        cpu.debug.clone()
+        let entry = self.clock(true);
+
+        self.machine.sync();
+        entry.map(|entry| entry.to_string()).unwrap_or_default()
     }
 
+    // runs until the ppu reaches vblank, or until the debugger stops it
+    // part way through, in which case the next call picks up where it
+    // stopped
     pub fn tick_frame(&mut self) {
-        let cpu = &mut self.bus.cpu;
-        let ppu = &mut self.bus.ppu;
+        if self.debugger.paused() {
+            return;
+        }
+
+        let end = match self.frame_end {
+            Some(end) => end,
+            None => {
+                self.latch_input();
+                self.machine.bus.ppu.frame + 1
+            },
+        };
+
+        self.frame_end = Some(end);
+
+        let debugging = self.debugger.armed();
 
-        self.remaining_cycles = CYCLES_PER_FRAME;
+        // accesses made outside of emulation aren't the game's
+        if let Some(log) = &mut self.machine.bus.log {
+            log.clear();
+        }
 
-        while self.remaining_cycles > 0 {
-            // TODO: some nmi/irq stuff goes here (probably interrupts)
+        while self.machine.bus.ppu.frame < end {
+            if debugging && self.check_instruction() {
+                self.machine.sync();
+                return;
+            }
 
-            cpu.borrow_mut().tick();
+            self.clock(false);
 
-            for _ in 0..ppu::TICKS_PER_CYCLE {
-                ppu.borrow_mut().tick();
+            if debugging && self.check_watch() {
+                break;
             }
+        }
+
+        self.machine.sync();
+
+        if self.machine.bus.ppu.frame >= end {
+            self.frame_end = None;
+            self.end_frame();
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn region(&self) -> Region {
+        self.machine.region()
+    }
 
-            self.remaining_cycles -= 1;
+    pub fn set_region(&mut self, region: Region) {
+        self.machine.set_region(region);
+    }
+
+    // frames per second, for pacing the frontend
+    pub fn frame_rate(&self) -> f64 {
+        self.machine.region().frame_rate()
+    }
+
+    pub fn ppu_sync(&self) -> PpuSync {
+        self.machine.ppu_sync()
+    }
+
+    pub fn set_ppu_sync(&mut self, mode: PpuSync) {
+        self.machine.set_ppu_sync(mode);
+    }
+
+    // breaks before executing `addr`, when `condition` (if any) holds
+    pub fn add_breakpoint(&mut self, addr: u16, condition: Option<String>) -> Result<(), ExprError> {
+        let condition = condition.as_deref().map(Expr::parse).transpose()?;
+
+        self.debugger.breakpoints.insert(addr, condition);
+        Ok(())
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.debugger.breakpoints.remove(&addr);
+    }
+
+    // breaks after an access to `start..=end`, conditions can use `addr`
+    // and `value` for the access itself
+    pub fn add_watchpoint(
+        &mut self,
+        start: u16,
+        end: u16,
+        read: bool,
+        write: bool,
+        condition: Option<String>,
+    ) -> Result<(), ExprError> {
+        let condition = condition.as_deref().map(Expr::parse).transpose()?;
+
+        // only watched buses pay for logging their accesses
+        self.machine.bus.log.get_or_insert_with(Vec::new);
+
+        self.debugger.watchpoints.push(Watchpoint { start, end, read, write, condition });
+        Ok(())
+    }
+
+    pub fn remove_watchpoint(&mut self, start: u16, end: u16, read: bool, write: bool) {
+        self.debugger.watchpoints.retain(|point| {
+            (point.start, point.end, point.read, point.write) != (start, end, read, write)
+        });
+
+        if self.debugger.watchpoints.is_empty() {
+            self.clear_watchpoints();
         }
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.debugger.watchpoints.clear();
+        self.machine.bus.log = None;
+    }
+
+    pub fn set_break_on(&mut self, brk: bool, unknown_opcode: bool, nmi: bool, irq: bool) {
+        self.debugger.break_on_brk = brk;
+        self.debugger.break_on_unknown = unknown_opcode;
+        self.debugger.break_on_nmi = nmi;
+        self.debugger.break_on_irq = irq;
+    }
 
-        // TODO: Apu tick
+    pub fn pause(&mut self) {
+        self.debugger.pause();
     }
 
+    pub fn resume(&mut self) {
+        self.debugger.resume();
+    }
+
+    pub fn paused(&self) -> bool {
+        self.debugger.paused()
+    }
+
+    pub fn break_reason(&self) -> Option<String> {
+        self.debugger.reason.as_ref().map(BreakReason::to_string)
+    }
+
+    // the steps below resume emulation, the next `tick_frame` runs until
+    // they're done
+    pub fn step_into(&mut self) {
+        self.debugger.step_into();
+    }
+
+    pub fn step_over(&mut self) {
+        self.debugger.step_over(&self.machine.cpu, &self.machine.bus);
+    }
+
+    pub fn step_out(&mut self) {
+        self.debugger.step_out(&self.machine.cpu);
+    }
+
+    pub fn run_to_scanline(&mut self, line: u16) {
+        self.debugger.run_to_scanline(line);
+    }
+
+    pub fn enable_rewind(&mut self, interval: u32, budget: usize) {
+        let mut rewind = Rewind::new(interval, budget);
+
+        rewind.push(self.frame, &self.save_state());
+        self.rewind = Some(rewind);
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    // steps back to the newest snapshot at least `frames` frames ago,
+    // doing nothing when history doesn't go back that far
+    pub fn rewind(&mut self, frames: u32) -> bool {
+        let target = self.frame.saturating_sub(frames as u64);
+        let snapshot = self.rewind.as_mut().and_then(|rewind| rewind.seek(target));
+
+        match snapshot {
+            Some((frame, state)) => {
+                if self.load_state(&state).is_err() {
+                    return false;
+                }
+
+                self.frame = frame;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.machine.bus.input.set_mode(mode);
+    }
+
+    pub fn set_buttons(&mut self, player: usize, buttons: u8) {
+        if player < input::PLAYERS {
+            self.machine.bus.input.buttons[player] = buttons;
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+
+        w.write_bool(self.frame_end.is_some());
+        w.write_u64(self.frame_end.unwrap_or(0));
+        self.machine.save(&mut w);
+
+        w.finish()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(state)?;
+        let backup = self.save_state();
+
+        // up to now, so what's dropped below is exactly what comes after
+        // the state
+        self.capture_audio();
+
+        if let Err(error) = self.load_snapshot(&mut r) {
+            // put back the machine as it was before the failed load
+            self.load_snapshot(&mut StateReader::new(&backup)?)?;
+            return Err(error);
+        }
+
+        // what was recorded after the state was saved never happened now
+        if let Some(audio) = &mut self.audio {
+            audio.rewind(self.machine.master_clock(), self.machine.region().master_clock());
+        }
+
+        Ok(())
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.machine.bus.cartridge.as_ref().is_some_and(|cartridge| cartridge.battery)
+    }
+
+    // battery backed prg-ram, empty when the cartridge has no battery
+    pub fn sram(&self) -> Vec<u8> {
+        match &self.machine.bus.cartridge {
+            Some(cartridge) => cartridge.sram().map(|sram| sram.to_vec()).unwrap_or_default(),
+            None => vec![],
+        }
+    }
+
+    pub fn load_sram(&mut self, sram: &[u8]) -> bool {
+        match &mut self.machine.bus.cartridge {
+            Some(cartridge) => cartridge.load_sram(sram),
+            None => false,
+        }
+    }
+
+    pub fn sram_dirty(&self) -> bool {
+        self.machine.bus.cartridge.as_ref().is_some_and(|cartridge| cartridge.sram_dirty)
+    }
+
+    // call once the frontend has persisted the result of `sram()`
+    pub fn mark_sram_saved(&mut self) {
+        if let Some(cartridge) = &mut self.machine.bus.cartridge {
+            cartridge.sram_dirty = false;
+        }
+    }
+
+    pub fn record_movie(&mut self, from_power_on: bool) {
+        let mut movie = Movie::new();
+
+        movie.four_score = self.machine.bus.input.mode == InputMode::FourScore;
+        movie.region = self.machine.region();
+
+        if from_power_on {
+            self.movie = None;
+            self.power_on();
+        } else {
+            movie.start = Some(self.save_state());
+        }
+
+        self.movie = Some(Playback::Recording { movie, commands: 0 });
+    }
+
+    pub fn play_fm2(&mut self, fm2: &str) -> Result<(), MovieError> {
+        self.play_movie(Movie::from_fm2(fm2)?)
+    }
+
+    pub fn export_fm2(&self) -> Result<String, MovieError> {
+        match &self.movie {
+            Some(playback) => playback.movie().to_fm2(),
+            None => Err(MovieError::Inactive),
+        }
+    }
+
+    pub fn movie_playing(&self) -> bool {
+        matches!(self.movie, Some(Playback::Playing { .. }))
+    }
+
+    pub fn stop_movie(&mut self) {
+        self.movie = None;
+    }
+
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.tracer = if enabled {
+            Some(Box::new(self.trace_buffer.clone()))
+        } else {
+            None
+        };
+    }
+
+    // drains the lines collected since the last call
+    pub fn take_trace(&mut self) -> String {
+        self.trace_buffer.take().join("\n")
+    }
+
+    // listing of `start..=end` of the cpu address space
+    pub fn disassemble(&self, start: u16, end: u16) -> String {
+        disasm::to_text(&disasm::disassemble_bus(&self.machine.bus, start, end))
+    }
+
+    // the screen as rgba, see `viewer::screen`
+    pub fn screen(&self) -> Vec<u8> {
+        viewer::screen(&self.machine.bus)
+    }
+
+    // a png of `screen`. there's no rendered framebuffer yet, so like it
+    // this is rebuilt from the nametables, palettes and oam at the end of
+    // the frame rather than read from what the ppu output. mid frame
+    // changes to scroll, palettes or oam don't show up
+    pub fn screenshot(&self) -> Vec<u8> {
+        png::encode(viewer::SCREEN_WIDTH as u32, viewer::SCREEN_HEIGHT as u32, &self.screen())
+    }
+
+    // identifies the frame `screen` rebuilds from nametable and oam state,
+    // for comparing against known good ones. it never sees the ppu's
+    // rendering, so it can't catch regressions there, and two frames the
+    // ppu drew differently mid frame can hash the same
+    pub fn reconstructed_frame_hash(&self) -> u64 {
+        checksum::fnv1a64(&self.screen())
+    }
+
+    // rgba views of ppu memory for debugging, sizes are in `viewer`
+    pub fn pattern_table(&self, table: usize, palette: usize) -> Vec<u8> {
+        viewer::pattern_table(&self.machine.bus, table, palette)
+    }
+
+    pub fn nametables(&self) -> Vec<u8> {
+        viewer::nametables(&self.machine.bus)
+    }
+
+    pub fn sprite_sheet(&self) -> Vec<u8> {
+        viewer::sprite_sheet(&self.machine.bus)
+    }
+
+    pub fn sprites(&self) -> Vec<viewer::Sprite> {
+        viewer::sprites(&self.machine.bus.ppu)
+    }
+
+    pub fn palette_ram(&self) -> Vec<u8> {
+        viewer::palette_ram(&self.machine.bus)
+    }
+
+    pub fn memory_size(&self, space: MemorySpace) -> usize {
+        self.machine.bus.memory_size(space)
+    }
+
+    // side effect free access to any address space, see `inspect`
+    pub fn peek(&self, space: MemorySpace, addr: usize) -> Option<u8> {
+        self.machine.bus.peek_memory(space, addr)
+    }
+
+    pub fn peek_range(&self, space: MemorySpace, start: usize, len: usize) -> Vec<u8> {
+        self.machine.bus.peek_memory_range(space, start, len)
+    }
+
+    pub fn poke(&mut self, space: MemorySpace, addr: usize, value: u8) -> bool {
+        self.machine.bus.poke_memory(space, addr, value)
+    }
+
+    pub fn hex_dump(&self, space: MemorySpace, start: usize, len: usize) -> String {
+        inspect::hex_dump(&self.machine.bus.peek_memory_range(space, start, len), start)
+    }
+
+    // these go through the bus like the cpu's own accesses, registers
+    // and all, use `peek`/`poke` to look at memory
     pub fn write(&mut self, addr: usize, value: u8) {
-        self.bus.write(addr, value);
+        self.machine.bus.write(addr, value);
     }
 
     pub fn read(&mut self, addr: usize) -> u8 {
-        self.bus.read(addr)
+        self.machine.bus.read(addr)
+    }
+}
+
+impl Nes {
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.machine.cpu
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.machine.bus
+    }
+
+    pub fn set_trace_sink(&mut self, sink: Box<dyn TraceSink>) {
+        self.tracer = Some(sink);
+    }
+
+    // see `TraceSink::flush`, fine when nothing is tracing
+    pub fn flush_trace(&mut self) -> std::io::Result<()> {
+        match &mut self.tracer {
+            Some(tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // capture stays off the wasm api while the apu channels are missing,
+    // everything it records is silence. records from here on, replacing
+    // anything recorded before
+    pub fn start_audio_capture(&mut self, sample_rate: u32, stems: bool) {
+        self.audio = Some(AudioCapture::new(sample_rate, stems, self.machine.master_clock()));
+    }
+
+    pub fn stop_audio_capture(&mut self) {
+        self.audio = None;
+    }
+
+    // the mixed output recorded so far, empty when not recording. silent
+    // until the apu channels are emulated, see `audio::levels`
+    pub fn audio_wav(&mut self, format: SampleFormat) -> Vec<u8> {
+        self.capture_audio();
+
+        match &self.audio {
+            Some(audio) => wav::encode(&audio.mix, audio.sample_rate, format),
+            None => vec![],
+        }
+    }
+
+    // one channel on its own, `channel` indexing `audio::CHANNELS`. only
+    // there when the capture was started with stems
+    pub fn audio_stem_wav(&mut self, channel: usize, format: SampleFormat) -> Option<Vec<u8>> {
+        self.capture_audio();
+
+        let audio = self.audio.as_ref()?;
+        let stem = audio.stems.as_ref()?.get(channel)?;
+
+        Some(wav::encode(stem, audio.sample_rate, format))
+    }
+
+    pub fn audio_capture(&self) -> Option<&AudioCapture> {
+        self.audio.as_ref()
+    }
+
+    // samples everything up to where the machine is now
+    fn capture_audio(&mut self) {
+        if let Some(audio) = &mut self.audio {
+            let levels = audio::levels(&self.machine.bus);
+            audio.advance(self.machine.master_clock(), self.machine.region().master_clock(), levels);
+        }
+    }
+
+    fn clock(&mut self, capture: bool) -> Option<TraceEntry> {
+        let cpu = &self.machine.cpu;
+
+        // cycles servicing an interrupt or halted don't start an instruction
+        let starts = cpu.skip_ticks == 0 && !cpu.interrupt_pending() && cpu.halted.is_none();
+
+        let entry = if starts && (capture || self.tracer.is_some()) {
+            // traces show where the ppu is
+            self.machine.sync();
+
+            let Machine { cpu, bus, .. } = &self.machine;
+            Some(TraceEntry::capture(cpu, bus))
+        } else {
+            None
+        };
+
+        if let (Some(entry), Some(tracer)) = (&entry, &mut self.tracer) {
+            tracer.trace(entry);
+        }
+
+        self.machine.clock();
+
+        entry
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    // stops before the instruction about to start, if the debugger wants to
+    fn check_instruction(&mut self) -> bool {
+        let cpu = &self.machine.cpu;
+
+        if cpu.skip_ticks != 0 || cpu.halted.is_some() {
+            return false;
+        }
+
+        // conditions and run-to-scanline can look at the ppu
+        self.machine.sync();
+
+        let Machine { cpu, bus, .. } = &self.machine;
+        self.debugger.reason = self.debugger.check_instruction(cpu, bus);
+        self.debugger.paused()
+    }
+
+    fn check_watch(&mut self) -> bool {
+        self.machine.sync();
+
+        let Machine { cpu, bus, .. } = &mut self.machine;
+
+        self.debugger.reason = self.debugger.check_watch(cpu, bus);
+        self.debugger.paused()
+    }
+
+    fn end_frame(&mut self) {
+        self.capture_audio();
+        self.frame += 1;
+
+        if let Some(rewind) = &self.rewind {
+            if self.frame.is_multiple_of(rewind.interval as u64) {
+                let state = self.save_state();
+                self.rewind.as_mut().unwrap().push(self.frame, &state);
+            }
+        }
+    }
+
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), MovieError> {
+        self.movie = None;
+        self.set_region(movie.region);
+
+        match &movie.start {
+            Some(state) => self.load_state(state)?,
+            None => self.power_on(),
+        }
+
+        self.set_input_mode(if movie.four_score {
+            InputMode::FourScore
+        } else {
+            InputMode::Standard
+        });
+
+        self.movie = Some(Playback::Playing { movie, frame: 0 });
+        Ok(())
+    }
+
+    pub fn take_movie(&mut self) -> Option<Movie> {
+        match self.movie.take()? {
+            Playback::Recording { movie, .. } => Some(movie),
+            Playback::Playing { movie, .. } => Some(movie),
+        }
+    }
+
+    fn record_command(&mut self, command: u8) {
+        if let Some(Playback::Recording { commands, .. }) = &mut self.movie {
+            *commands |= command;
+        }
+    }
+
+    // input is sampled once, right before the frame starts running, so a
+    // recorded movie replays the exact same buttons on the exact same frame
+    fn latch_input(&mut self) {
+        let buttons = self.machine.bus.input.buttons;
+
+        let frame = match &mut self.movie {
+            Some(Playback::Recording { movie, commands }) => {
+                movie.frames.push(movie::Frame { commands: *commands, buttons });
+                *commands = 0;
+                return;
+            },
+            Some(Playback::Playing { movie, frame }) => {
+                *frame += 1;
+                movie.frames.get(*frame - 1).copied()
+            },
+            None => return,
+        };
+
+        match frame {
+            Some(frame) => {
+                if frame.commands & movie::COMMAND_HARD_RESET != 0 {
+                    self.power_on();
+                } else if frame.commands & movie::COMMAND_SOFT_RESET != 0 {
+                    self.reset();
+                }
+
+                self.machine.bus.input.buttons = frame.buttons;
+            },
+            // playback is over, hand control back to the player
+            None => self.movie = None,
+        }
+    }
+
+    fn load_snapshot(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let running = r.read_bool()?;
+        let end = r.read_u64()?;
+
+        self.frame_end = if running { Some(end) } else { None };
+        self.machine.load(r)?;
+
+        r.finish()
+    }
+}
+
+impl Default for Nes {
+    fn default() -> Nes {
+        Nes::new()
     }
 }
\ No newline at end of file
@@ -0,0 +1,244 @@
+use wasm_bindgen::prelude::*;
+use super::{
+    cpu::Cpu,
+    bus::{Bus, IRQ_FRAME_COUNTER},
+    region::Region,
+    scheduler::{Event, Scheduler},
+    state::{Snapshot, StateReader, StateWriter, StateError},
+};
+
+// how the ppu is kept in step with the cpu. catch-up only runs it when
+// something could notice, and gives the same results much faster
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PpuSync {
+    // every dot as soon as its master clock comes around
+    #[default]
+    LockStep,
+    // left behind until a register access, a mapper write or vblank
+    CatchUp,
+}
+
+// the whole console in one owned value, the cpu is handed the bus for
+// each cycle rather than holding on to it
+#[derive(Clone)]
+pub struct Machine {
+    pub cpu: Cpu,
+    pub bus: Bus,
+    pub scheduler: Scheduler,
+
+    region: Region,
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        let mut machine = Machine {
+            cpu: Cpu::new(),
+            bus: Bus::new(),
+            scheduler: Scheduler::new(),
+            region: Region::Ntsc,
+        };
+
+        machine.restart_frame_counter(0);
+        machine
+    }
+
+    // master clock ticks since power on
+    pub fn master_clock(&self) -> u64 {
+        self.scheduler.cpu
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.sync();
+
+        self.region = region;
+        self.bus.ppu.region = region;
+        self.bus.ppu_clock = self.scheduler.cpu;
+        self.restart_frame_counter(self.scheduler.cpu);
+        self.refresh_sync();
+    }
+
+    pub fn ppu_sync(&self) -> PpuSync {
+        if self.bus.catch_up { PpuSync::CatchUp } else { PpuSync::LockStep }
+    }
+
+    pub fn set_ppu_sync(&mut self, mode: PpuSync) {
+        self.sync();
+
+        self.bus.catch_up = mode == PpuSync::CatchUp;
+        self.refresh_sync();
+    }
+
+    // brings the ppu up to the cpu, for anything looking at it from
+    // outside of emulation
+    pub fn sync(&mut self) {
+        self.bus.run_ppu(self.scheduler.cpu);
+        self.poll_nmi();
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset(&mut self.bus);
+        self.restart_frame_counter(self.scheduler.cpu);
+    }
+
+    // puts every component back into its power on state, keeping the
+    // inserted cartridge, the region and the selected input mode
+    pub fn power_on(&mut self) {
+        self.cpu = Cpu::new();
+        self.bus.power_on();
+        self.scheduler = Scheduler::new();
+        self.restart_frame_counter(0);
+        self.refresh_sync();
+    }
+
+    // one cpu cycle, then the ppu dots and events that start before the
+    // next one
+    pub fn clock(&mut self) {
+        let start = self.scheduler.cpu;
+
+        self.bus.now = start;
+        self.cpu.tick(&mut self.bus);
+        self.scheduler.cpu += self.region.cpu_divider();
+
+        if let Some(page) = self.bus.dma.take() {
+            self.scheduler.schedule(start, Event::OamDma(page));
+        }
+
+        if self.bus.frame_counter.restart {
+            self.restart_frame_counter(start);
+        }
+
+        if self.bus.synced {
+            self.refresh_sync();
+        }
+
+        self.run_until(self.scheduler.cpu);
+        self.poll_nmi();
+        self.cpu.irq = self.bus.irq != 0;
+    }
+
+    fn run_until(&mut self, end: u64) {
+        loop {
+            // a dot starting on the same clock as an event goes first
+            let next = self.scheduler.next_event().unwrap_or(u64::MAX);
+
+            if !self.bus.catch_up {
+                self.bus.run_ppu(end.min(next.saturating_add(1)));
+            }
+
+            match self.scheduler.pop(end) {
+                Some((time, event)) => {
+                    self.bus.now = time + 1;
+                    self.dispatch(time, event);
+
+                    if self.bus.synced {
+                        self.refresh_sync();
+                    }
+                },
+                None => break,
+            }
+        }
+    }
+
+    fn poll_nmi(&mut self) {
+        if self.bus.ppu.nmi {
+            self.bus.ppu.nmi = false;
+            self.cpu.nmi = true;
+        }
+    }
+
+    // queues a sync for the dot that starts vblank, which is the only
+    // thing the ppu does on its own that the rest of the console sees.
+    // register writes can move it, so it's worked out again after them
+    fn refresh_sync(&mut self) {
+        self.bus.synced = false;
+        self.scheduler.cancel(Event::PpuSync);
+
+        if self.bus.catch_up {
+            let dots = self.bus.ppu.dots_to_vblank();
+            let time = self.bus.ppu_clock + (dots - 1) * self.region.ppu_divider();
+
+            self.scheduler.schedule(time, Event::PpuSync);
+        }
+    }
+
+    fn dispatch(&mut self, time: u64, event: Event) {
+        match event {
+            Event::PpuSync => {
+                self.bus.run_ppu(time + 1);
+                self.bus.synced = true;
+            },
+            Event::OamDma(page) => self.cpu.oam_dma(&mut self.bus, page),
+            Event::FrameCounter => {
+                let counter = &mut self.bus.frame_counter;
+                let (steps, length) = self.region.frame_counter_steps(counter.five_step);
+
+                if counter.irq() {
+                    self.bus.irq |= IRQ_FRAME_COUNTER;
+                }
+
+                let next = (counter.step + 1) % steps.len();
+
+                let cycles = if next == 0 {
+                    length - steps[counter.step] + steps[0]
+                } else {
+                    steps[next] - steps[counter.step]
+                };
+
+                counter.step = next;
+                self.scheduler.schedule(time + cycles * self.region.cpu_divider(), Event::FrameCounter);
+            },
+            Event::Irq { source, asserted: true } => self.bus.irq |= source,
+            Event::Irq { source, asserted: false } => self.bus.irq &= !source,
+        }
+    }
+
+    // writes to $4017 take effect a few cycles late on hardware, here the
+    // sequence starts over from the write itself
+    fn restart_frame_counter(&mut self, time: u64) {
+        let counter = &mut self.bus.frame_counter;
+        let (steps, _) = self.region.frame_counter_steps(counter.five_step);
+
+        counter.restart = false;
+        counter.step = 0;
+
+        self.scheduler.cancel(Event::FrameCounter);
+        self.scheduler.schedule(time + steps[0] * self.region.cpu_divider(), Event::FrameCounter);
+    }
+}
+
+impl Default for Machine {
+    fn default() -> Machine {
+        Machine::new()
+    }
+}
+
+impl Snapshot for Machine {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_u8(self.region.to_u8());
+
+        self.scheduler.save(w);
+        self.cpu.save(w);
+        self.bus.save(w);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let region = Region::from_u8(r.read_u8()?).ok_or(StateError::Invalid("region"))?;
+
+        self.region = region;
+        self.bus.ppu.region = region;
+
+        self.scheduler.load(r)?;
+        self.cpu.load(r)?;
+        self.bus.load(r)?;
+
+        self.sync();
+        self.refresh_sync();
+
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
-use super::bus::{BusRead, BusWrite};
+use super::{
+    bus::{BusRead, BusWrite},
+    state::{Snapshot, StateReader, StateWriter, StateError},
+};
 
 pub const SIZE: usize = 2 * 1024;
 
+#[derive(Clone)]
 pub struct Memory {
     pub data: [u8; SIZE],
 }
@@ -12,10 +16,20 @@ impl Memory {
     }
 }
 
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
 impl BusRead for Memory {
-    fn read(&self, addr: usize) -> Option<u8> {
+    fn read(&mut self, addr: usize) -> Option<u8> {
+        self.peek(addr)
+    }
+
+    fn peek(&self, addr: usize) -> Option<u8> {
         if addr <= 0x1FFF {
-            Some(self.data[(addr & 0x07FF) as usize])
+            Some(self.data[addr & 0x07FF])
         } else {
             None
         }
@@ -25,10 +39,20 @@ impl BusRead for Memory {
 impl BusWrite for Memory {
     fn write(&mut self, addr: usize, value: u8) -> bool {
         if addr <= 0x1FFF {
-            self.data[(addr & 0x07FF) as usize] = value;
+            self.data[addr & 0x07FF] = value;
             true
         } else {
             false
         }
     }
+}
+
+impl Snapshot for Memory {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.data);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.read_into(&mut self.data)
+    }
 }
\ No newline at end of file
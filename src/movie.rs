@@ -0,0 +1,219 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+use super::{input::PLAYERS, region::Region, state::StateError};
+
+pub const COMMAND_SOFT_RESET: u8 = 0b00000001;
+pub const COMMAND_HARD_RESET: u8 = 0b00000010;
+
+// fm2 button columns, leftmost is the highest bit of our button byte
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MovieError {
+    Parse(usize, String),
+    Unsupported(&'static str),
+    Inactive,
+    State(StateError),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Parse(line, reason) => write!(f, "movie line {}: {}", line, reason),
+            MovieError::Unsupported(what) => write!(f, "movie uses unsupported {}", what),
+            MovieError::Inactive => write!(f, "no movie is being recorded or played"),
+            MovieError::State(error) => write!(f, "movie start state: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(error: StateError) -> MovieError {
+        MovieError::State(error)
+    }
+}
+
+impl From<MovieError> for JsValue {
+    fn from(error: MovieError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Frame {
+    pub commands: u8,
+    pub buttons: [u8; PLAYERS],
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Movie {
+    // save state the movie begins from, power on when empty
+    pub start: Option<Vec<u8>>,
+    pub four_score: bool,
+    pub region: Region,
+    pub rerecords: u32,
+    pub rom_filename: String,
+    pub frames: Vec<Frame>,
+}
+
+pub enum Playback {
+    // commands are collected until the next frame is recorded
+    Recording { movie: Movie, commands: u8 },
+    Playing { movie: Movie, frame: usize },
+}
+
+impl Playback {
+    pub fn movie(&self) -> &Movie {
+        match self {
+            Playback::Recording { movie, .. } => movie,
+            Playback::Playing { movie, .. } => movie,
+        }
+    }
+}
+
+impl Movie {
+    pub fn new() -> Movie {
+        Movie::default()
+    }
+
+    pub fn from_fm2(text: &str) -> Result<Movie, MovieError> {
+        let mut movie = Movie::new();
+        let mut ports = [1, 1];
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            let number = i + 1;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('|') {
+                movie.frames.push(parse_frame(line, movie.four_score, ports)
+                    .map_err(|reason| MovieError::Parse(number, reason))?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let flag = || value.trim() == "1";
+            let int = || value.trim().parse::<u32>()
+                .map_err(|_| MovieError::Parse(number, format!("bad value for {}", key)));
+
+            match key {
+                "version" if int()? != 3 => return Err(MovieError::Unsupported("fm2 version")),
+                "binary" if flag() => return Err(MovieError::Unsupported("binary fm2")),
+                "palFlag" => movie.region = if flag() { Region::Pal } else { Region::Ntsc },
+                "FDS" if flag() => return Err(MovieError::Unsupported("famicom disk system")),
+                "savestate" => return Err(MovieError::Unsupported("fceux save state")),
+                "fourscore" => movie.four_score = flag(),
+                "rerecordCount" => movie.rerecords = int()?,
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "port0" | "port1" => {
+                    let port = int()?;
+
+                    // 0 = nothing plugged in, 1 = gamepad, 2 = zapper
+                    if port > 1 {
+                        return Err(MovieError::Unsupported("zapper"));
+                    }
+
+                    ports[(key == "port1") as usize] = port;
+                },
+                _ => {},
+            }
+        }
+
+        Ok(movie)
+    }
+
+    pub fn to_fm2(&self) -> Result<String, MovieError> {
+        if self.start.is_some() {
+            return Err(MovieError::Unsupported("save state start in fm2 export"));
+        }
+
+        if self.region == Region::Dendy {
+            return Err(MovieError::Unsupported("dendy timing in fm2 export"));
+        }
+
+        let mut fm2 = String::new();
+
+        fm2.push_str("version 3\n");
+        fm2.push_str("emuVersion 0\n");
+        fm2.push_str(&format!("rerecordCount {}\n", self.rerecords));
+        fm2.push_str(&format!("palFlag {}\n", (self.region == Region::Pal) as u8));
+        fm2.push_str(&format!("romFilename {}\n", self.rom_filename));
+        fm2.push_str(&format!("fourscore {}\n", self.four_score as u8));
+        fm2.push_str("microphone 0\n");
+        fm2.push_str("port0 1\n");
+        fm2.push_str("port1 1\n");
+        fm2.push_str("port2 0\n");
+        fm2.push_str("FDS 0\n");
+        fm2.push_str("NewPPU 0\n");
+
+        let gamepads = if self.four_score { 4 } else { 2 };
+
+        for frame in self.frames.iter() {
+            fm2.push_str(&format!("|{}|", frame.commands));
+
+            for buttons in frame.buttons.iter().take(gamepads) {
+                fm2.push_str(&format_buttons(*buttons));
+                fm2.push('|');
+            }
+
+            // expansion port column
+            fm2.push_str("|\n");
+        }
+
+        Ok(fm2)
+    }
+}
+
+fn parse_frame(line: &str, four_score: bool, ports: [u32; 2]) -> Result<Frame, String> {
+    let mut fields = line[1..].split('|');
+    let commands = fields.next()
+        .and_then(|field| field.trim().parse().ok())
+        .ok_or("bad command field")?;
+
+    let mut frame = Frame { commands, buttons: [0; PLAYERS] };
+
+    if four_score {
+        for buttons in frame.buttons.iter_mut() {
+            let field = fields.next().ok_or("missing gamepad field")?;
+            *buttons = parse_buttons(field)?;
+        }
+    } else {
+        for (port, kind) in ports.iter().enumerate() {
+            // a port with nothing plugged in still has an empty column
+            let field = fields.next().ok_or("missing gamepad field")?;
+
+            if *kind != 0 {
+                frame.buttons[port] = parse_buttons(field)?;
+            }
+        }
+    }
+
+    Ok(frame)
+}
+
+pub fn parse_buttons(field: &str) -> Result<u8, String> {
+    if field.len() != FM2_BUTTONS.len() {
+        return Err(format!("gamepad field `{}` should be 8 wide", field));
+    }
+
+    let mut buttons = 0;
+
+    for (i, c) in field.bytes().enumerate() {
+        if c != b'.' && c != b' ' {
+            buttons |= 0b10000000 >> i;
+        }
+    }
+
+    Ok(buttons)
+}
+
+fn format_buttons(buttons: u8) -> String {
+    FM2_BUTTONS.iter().enumerate()
+        .map(|(i, c)| if buttons & (0b10000000 >> i) != 0 { *c as char } else { '.' })
+        .collect()
+}
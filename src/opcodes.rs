@@ -0,0 +1,139 @@
+// addressing modes, each decides how the operand bytes are used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Accumulator,
+    Implied,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Relative,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+}
+
+impl Mode {
+    // instruction length in bytes, opcode included
+    pub fn size(self) -> usize {
+        match self {
+            Mode::Accumulator | Mode::Implied => 1,
+            Mode::Absolute | Mode::AbsoluteX | Mode::AbsoluteY | Mode::Indirect => 3,
+            _ => 2,
+        }
+    }
+}
+
+// (mnemonic, addressing mode, base cycles)
+pub type Instruction = (&'static str, Mode, u64);
+
+pub static OPCODES: [Option<Instruction>; 256] = build();
+
+pub fn get(opcode: usize) -> Option<Instruction> {
+    OPCODES[opcode & 0xff]
+}
+
+const fn build() -> [Option<Instruction>; 256] {
+    let mut table = [None; 256];
+    let mut opcode = 0;
+
+    while opcode < 256 {
+        table[opcode] = decode(opcode);
+        opcode += 1;
+    }
+
+    table
+}
+
+const fn decode(opcode: usize) -> Option<Instruction> {
+    Some(match opcode {
+        // ADC
+        0x69 => ("ADC", Mode::Immediate, 2),
+        0x65 => ("ADC", Mode::ZeroPage, 3),
+        0x75 => ("ADC", Mode::ZeroPageX, 4),
+        0x6d => ("ADC", Mode::Absolute, 4),
+        0x7d => ("ADC", Mode::AbsoluteX, 4),
+        0x79 => ("ADC", Mode::AbsoluteY, 4),
+        0x61 => ("ADC", Mode::IndirectX, 6),
+        0x71 => ("ADC", Mode::IndirectY, 5),
+
+        // AND
+        0x29 => ("AND", Mode::Immediate, 2),
+        0x25 => ("AND", Mode::ZeroPage, 3),
+        0x35 => ("AND", Mode::ZeroPageX, 4),
+        0x2d => ("AND", Mode::Absolute, 4),
+        0x3d => ("AND", Mode::AbsoluteX, 4),
+        0x39 => ("AND", Mode::AbsoluteY, 4),
+        0x21 => ("AND", Mode::IndirectX, 6),
+        0x31 => ("AND", Mode::IndirectY, 5),
+
+        // ASL
+        0x0a => ("ASL", Mode::Accumulator, 2),
+        0x06 => ("ASL", Mode::ZeroPage, 5),
+        0x16 => ("ASL", Mode::ZeroPageX, 6),
+        0x0e => ("ASL", Mode::Absolute, 6),
+        0x1e => ("ASL", Mode::AbsoluteX, 7),
+
+        // BCC
+        0x90 => ("BCC", Mode::Relative, 2),
+
+        // BCS
+        0xb0 => ("BCS", Mode::Relative, 2),
+
+        // BEQ
+        0xf0 => ("BEQ", Mode::Relative, 2),
+
+        // BIT
+        0x24 => ("BIT", Mode::ZeroPage, 3),
+        0x2c => ("BIT", Mode::Absolute, 4),
+
+        // BMI
+        0x30 => ("BMI", Mode::Relative, 2),
+
+        // BNE
+        0xd0 => ("BNE", Mode::Relative, 2),
+
+        // BPL
+        0x10 => ("BPL", Mode::Relative, 2),
+
+        // BRK
+        0x00 => ("BRK", Mode::Implied, 7),
+
+        // BVC
+        0x50 => ("BVC", Mode::Relative, 2),
+
+        // BVS
+        0x70 => ("BVS", Mode::Relative, 2),
+
+        // Clear Flags
+        0x18 => ("CLC", Mode::Implied, 2),
+        0xd8 => ("CLD", Mode::Implied, 2),
+        0x58 => ("CLI", Mode::Implied, 2),
+        0xb8 => ("CLV", Mode::Implied, 2),
+
+        // CMP
+        0xc9 => ("CMP", Mode::Immediate, 2),
+        0xc5 => ("CMP", Mode::ZeroPage, 3),
+        0xd5 => ("CMP", Mode::ZeroPageX, 4),
+        0xcd => ("CMP", Mode::Absolute, 4),
+        0xdd => ("CMP", Mode::AbsoluteX, 4),
+        0xd9 => ("CMP", Mode::AbsoluteY, 4),
+        0xc1 => ("CMP", Mode::IndirectX, 6),
+        0xd1 => ("CMP", Mode::IndirectY, 5),
+
+        // CPX
+        0xe0 => ("CPX", Mode::Immediate, 2),
+        0xe4 => ("CPX", Mode::ZeroPage, 3),
+        0xec => ("CPX", Mode::Absolute, 4),
+
+        // CPY
+        0xc0 => ("CPY", Mode::Immediate, 2),
+        0xc4 => ("CPY", Mode::ZeroPage, 3),
+        0xcc => ("CPY", Mode::Absolute, 4),
+
+        _ => return None,
+    })
+}
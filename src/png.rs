@@ -0,0 +1,93 @@
+use super::checksum::crc32;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// deflate's stored blocks hold at most this much
+const MAX_STORED: usize = 0xffff;
+
+// encodes an 8 bit rgba image, stored uncompressed so no deflate
+// implementation is needed, only the zlib framing around it
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let stride = width as usize * 4;
+
+    assert_eq!(rgba.len(), stride * height as usize, "image size doesn't match");
+
+    let mut header = vec![];
+
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, rgba, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    // every row starts with its filter type, 0 being none
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+
+    for row in rgba.chunks(stride.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate, 32k window, no preset dictionary, fastest
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(MAX_STORED).collect();
+
+    if blocks.is_empty() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    for (i, block) in blocks.iter().enumerate() {
+        let len = block.len() as u16;
+
+        out.push((i == blocks.len() - 1) as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // the sums can't overflow within this many bytes
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(path: &std::path::Path, width: u32, height: u32, rgba: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, encode(width, height, rgba))
+}
@@ -1,15 +1,354 @@
-use super::Tick;
+use super::{
+    Tick,
+    cartridge::{Cartridge, Mirroring},
+    region::Region,
+    state::{Snapshot, StateReader, StateWriter, StateError},
+};
 
-pub const TICKS_PER_CYCLE: u64 = 3;
+pub const DOTS_PER_SCANLINE: u16 = 341;
 
-pub struct Ppu;
+pub const CIRAM_SIZE: usize = 2 * 1024;
+pub const PALETTE_SIZE: usize = 32;
+pub const OAM_SIZE: usize = 256;
+
+pub const CTRL_NAMETABLE: u8 = 0b00000011;
+pub const CTRL_INCREMENT: u8 = 0b00000100;
+pub const CTRL_SPRITE_TABLE: u8 = 0b00001000;
+pub const CTRL_BACKGROUND_TABLE: u8 = 0b00010000;
+pub const CTRL_SPRITE_SIZE: u8 = 0b00100000;
+pub const CTRL_NMI: u8 = 0b10000000;
+
+pub const MASK_BACKGROUND: u8 = 0b00001000;
+pub const MASK_SPRITES: u8 = 0b00010000;
+
+pub const STATUS_VBLANK: u8 = 0b10000000;
+
+#[derive(Clone)]
+pub struct Ppu {
+    // decides how many scanlines a frame has
+    pub region: Region,
+
+    pub scanline: u16,
+    pub dot: u16,
+    // counts up as vblank starts, which is where a frame ends
+    pub frame: u64,
+    // raised at the start of vblank when enabled, for the cpu to pick up
+    pub nmi: bool,
+
+    // $2000, $2001, $2002 and $2003
+    pub ctrl: u8,
+    pub mask: u8,
+    pub status: u8,
+    pub oam_addr: u8,
+
+    // loopy's internal registers: the current and temporary vram address,
+    // fine x scroll and the shared $2005/$2006 write toggle
+    pub v: u16,
+    pub t: u16,
+    pub x: u8,
+    pub w: bool,
+
+    // $2007 reads below the palette lag a byte behind
+    pub buffer: u8,
+
+    pub ciram: [u8; CIRAM_SIZE],
+    pub palette: [u8; PALETTE_SIZE],
+    pub oam: [u8; OAM_SIZE],
+}
 
 impl Ppu {
     pub fn new() -> Ppu {
-        Ppu
+        Ppu {
+            region: Region::Ntsc,
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            nmi: false,
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_addr: 0,
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            buffer: 0,
+            ciram: [0; CIRAM_SIZE],
+            palette: [0; PALETTE_SIZE],
+            oam: [0; OAM_SIZE],
+        }
+    }
+
+    // reads the ppu's own address space, without side effects. pattern
+    // tables and nametable mirroring live on the cartridge
+    pub fn read_vram(&self, cartridge: Option<&Cartridge>, addr: u16) -> u8 {
+        let addr = addr & 0x3fff;
+
+        match addr {
+            0x0000..=0x1fff => cartridge.map_or(0, |cartridge| cartridge.chr[addr as usize]),
+            0x2000..=0x3eff => self.ciram[ciram_index(cartridge, addr)],
+            _ => self.palette[palette_index(addr)],
+        }
+    }
+
+    pub fn write_vram(&mut self, cartridge: Option<&mut Cartridge>, addr: u16, value: u8) {
+        let addr = addr & 0x3fff;
+
+        match addr {
+            0x0000..=0x1fff => {
+                if let Some(cartridge) = cartridge {
+                    // chr rom ignores writes
+                    if cartridge.chr_ram {
+                        cartridge.chr[addr as usize] = value;
+                    }
+                }
+            },
+            0x2000..=0x3eff => self.ciram[ciram_index(cartridge.as_deref(), addr)] = value,
+            _ => self.palette[palette_index(addr)] = value & 0x3f,
+        }
+    }
+
+    pub fn rendering(&self) -> bool {
+        self.mask & (MASK_BACKGROUND | MASK_SPRITES) != 0
+    }
+
+    // ticks until the one that starts vblank, assuming rendering stays as
+    // it is for the odd-frame skip
+    pub fn dots_to_vblank(&self) -> u64 {
+        let width = DOTS_PER_SCANLINE as u64;
+        let position = self.scanline as u64 * width + self.dot as u64;
+        let vblank = self.region.vblank_line() as u64 * width + 1;
+
+        if position < vblank {
+            return vblank - position;
+        }
+
+        let pre_render = self.region.scanlines() as u64 - 1;
+        let skip = position <= pre_render * width + width - 2
+            && self.frame & 1 == 1
+            && self.rendering()
+            && self.region.skips_odd_dot();
+
+        self.region.scanlines() as u64 * width - position + vblank - skip as u64
+    }
+
+    fn increment(&mut self) {
+        let step = if self.ctrl & CTRL_INCREMENT != 0 { 32 } else { 1 };
+        self.v = self.v.wrapping_add(step) & 0x7fff;
+    }
+
+    // registers are mirrored every 8 bytes through $3fff
+    pub fn read(&mut self, cartridge: Option<&Cartridge>, addr: usize) -> u8 {
+        let value = self.peek(cartridge, addr);
+
+        match addr & 7 {
+            2 => {
+                self.status &= !STATUS_VBLANK;
+                self.w = false;
+            },
+            7 => {
+                // palette reads are immediate, but still refill the buffer
+                // with the nametable byte underneath
+                let addr = self.v & 0x3fff;
+
+                self.buffer = if addr >= 0x3f00 {
+                    self.read_vram(cartridge, addr - 0x1000)
+                } else {
+                    self.read_vram(cartridge, addr)
+                };
+
+                self.increment();
+            },
+            _ => {},
+        }
+
+        value
+    }
+
+    pub fn peek(&self, cartridge: Option<&Cartridge>, addr: usize) -> u8 {
+        match addr & 7 {
+            2 => self.status,
+            4 => self.oam[self.oam_addr as usize],
+            7 => {
+                let addr = self.v & 0x3fff;
+
+                if addr >= 0x3f00 {
+                    self.read_vram(cartridge, addr)
+                } else {
+                    self.buffer
+                }
+            },
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, cartridge: Option<&mut Cartridge>, addr: usize, value: u8) {
+        let t = self.t;
+
+        match addr & 7 {
+            0 => {
+                // turning nmis on during vblank fires one straight away
+                if value & !self.ctrl & CTRL_NMI != 0 && self.status & STATUS_VBLANK != 0 {
+                    self.nmi = true;
+                }
+
+                self.ctrl = value;
+                self.t = (t & !0x0c00) | (((value & CTRL_NAMETABLE) as u16) << 10);
+            },
+            1 => self.mask = value,
+            3 => self.oam_addr = value,
+            4 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            },
+            5 => {
+                if !self.w {
+                    self.t = (t & !0x001f) | (value as u16 >> 3);
+                    self.x = value & 7;
+                } else {
+                    self.t = (t & !0x73e0) | (((value & 7) as u16) << 12) | (((value & 0xf8) as u16) << 2);
+                }
+
+                self.w = !self.w;
+            },
+            6 => {
+                if !self.w {
+                    self.t = (t & 0x00ff) | (((value & 0x3f) as u16) << 8);
+                } else {
+                    self.t = (t & 0xff00) | value as u16;
+                    self.v = self.t;
+                }
+
+                self.w = !self.w;
+            },
+            7 => {
+                self.write_vram(cartridge, self.v, value);
+                self.increment();
+            },
+            // status is read only
+            _ => {},
+        }
+    }
+}
+
+// the 4 logical nametables share 2k, four screen boards would bring
+// their own extra 2k which we don't have yet, so they mirror vertically
+fn ciram_index(cartridge: Option<&Cartridge>, addr: u16) -> usize {
+    let mirroring = cartridge.map_or(Mirroring::Horizontal, |cartridge| cartridge.mirroring);
+
+    let table = (addr as usize >> 10) & 3;
+    let offset = addr as usize & 0x03ff;
+
+    let page = match mirroring {
+        Mirroring::Horizontal => table >> 1,
+        Mirroring::Vertical | Mirroring::FourScreen => table & 1,
+    };
+
+    (page << 10) | offset
+}
+
+// $3f10/$3f14/$3f18/$3f1c mirror the backdrop entries below them
+fn palette_index(addr: u16) -> usize {
+    let index = addr as usize & 0x1f;
+
+    if index & 0x13 == 0x10 {
+        index & 0x0f
+    } else {
+        index
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Ppu {
+        Ppu::new()
     }
 }
 
 impl Tick for Ppu {
-    fn tick(&mut self) {}
-}
\ No newline at end of file
+    fn tick(&mut self) {
+        let pre_render = self.region.scanlines() - 1;
+
+        self.dot += 1;
+
+        // odd frames go straight from dot 339 of the pre-render line to the
+        // first visible one while rendering
+        if self.scanline == pre_render
+            && self.dot == DOTS_PER_SCANLINE - 1
+            && self.frame & 1 == 1
+            && self.rendering()
+            && self.region.skips_odd_dot()
+        {
+            self.dot += 1;
+        }
+
+        if self.dot == DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+
+            if self.scanline == self.region.scanlines() {
+                self.scanline = 0;
+            }
+        }
+
+        if self.dot == 1 {
+            if self.scanline == self.region.vblank_line() {
+                self.status |= STATUS_VBLANK;
+                self.frame += 1;
+                self.nmi |= self.ctrl & CTRL_NMI != 0;
+            } else if self.scanline == pre_render {
+                self.status &= !STATUS_VBLANK;
+            }
+        }
+    }
+}
+
+impl Snapshot for Ppu {
+    fn save(&self, w: &mut StateWriter) {
+        w.write_u16(self.scanline);
+        w.write_u16(self.dot);
+        w.write_u64(self.frame);
+        w.write_bool(self.nmi);
+
+        w.write_u8(self.ctrl);
+        w.write_u8(self.mask);
+        w.write_u8(self.status);
+        w.write_u8(self.oam_addr);
+        w.write_u16(self.v);
+        w.write_u16(self.t);
+        w.write_u8(self.x);
+        w.write_bool(self.w);
+        w.write_u8(self.buffer);
+
+        w.write_bytes(&self.ciram);
+        w.write_bytes(&self.palette);
+        w.write_bytes(&self.oam);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        // the region is loaded first, so its frame height is the right one
+        self.scanline = r.read_u16()?;
+        self.dot = r.read_u16()?;
+
+        if self.scanline >= self.region.scanlines() || self.dot >= DOTS_PER_SCANLINE {
+            return Err(StateError::Invalid("ppu position"));
+        }
+        self.frame = r.read_u64()?;
+        self.nmi = r.read_bool()?;
+
+        self.ctrl = r.read_u8()?;
+        self.mask = r.read_u8()?;
+        self.status = r.read_u8()?;
+        self.oam_addr = r.read_u8()?;
+        self.v = r.read_u16()?;
+        self.t = r.read_u16()?;
+        self.x = r.read_u8()?;
+        self.w = r.read_bool()?;
+        self.buffer = r.read_u8()?;
+
+        r.read_into(&mut self.ciram)?;
+        r.read_into(&mut self.palette)?;
+        r.read_into(&mut self.oam)?;
+
+        Ok(())
+    }
+}
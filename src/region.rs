@@ -0,0 +1,110 @@
+use wasm_bindgen::prelude::*;
+use super::ppu::DOTS_PER_SCANLINE;
+
+// the console variant being emulated. they share a cpu and ppu but run
+// them off different master clocks and dividers, and pal/dendy draw 50
+// more scanlines a frame
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    // famiclone timing: pal's frame with ntsc's 3 dots per cycle
+    Dendy,
+}
+
+impl Region {
+    // how the region is stored in save states, and what nes 2.0 headers
+    // use apart from 2 meaning "runs on either"
+    pub fn from_u8(value: u8) -> Option<Region> {
+        match value {
+            0 => Some(Region::Ntsc),
+            1 => Some(Region::Pal),
+            3 => Some(Region::Dendy),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => 3,
+        }
+    }
+
+    // in hz
+    pub fn master_clock(self) -> u64 {
+        match self {
+            Region::Ntsc => 21_477_272,
+            Region::Pal | Region::Dendy => 26_601_712,
+        }
+    }
+
+    // master clocks per cpu cycle
+    pub fn cpu_divider(self) -> u64 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    // master clocks per ppu dot
+    pub fn ppu_divider(self) -> u64 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5,
+        }
+    }
+
+    pub fn scanlines(self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    // dendy's extra lines come before vblank instead of during it
+    pub fn vblank_line(self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    // only the ntsc ppu drops a dot on odd frames
+    pub fn skips_odd_dot(self) -> bool {
+        self == Region::Ntsc
+    }
+
+    // cpu cycles from a $4017 write to each step of the frame counter, and
+    // the length of the whole sequence. dendy keeps ntsc's apu
+    pub fn frame_counter_steps(self, five_step: bool) -> ([u64; 4], u64) {
+        match (self, five_step) {
+            (Region::Pal, false) => ([8313, 16627, 24939, 33253], 33254),
+            (Region::Pal, true) => ([8313, 16627, 24939, 41565], 41566),
+            (_, false) => ([7457, 14913, 22371, 29829], 29830),
+            (_, true) => ([7457, 14913, 22371, 37281], 37282),
+        }
+    }
+
+    // cpu cycles per second, what audio is sampled against
+    pub fn cpu_clock(self) -> f64 {
+        self.master_clock() as f64 / self.cpu_divider() as f64
+    }
+
+    pub fn master_clocks_per_frame(self) -> u64 {
+        self.scanlines() as u64 * DOTS_PER_SCANLINE as u64 * self.ppu_divider()
+    }
+
+    // rounded up, a frame doesn't always end on a cycle boundary
+    pub fn cycles_per_frame(self) -> u64 {
+        self.master_clocks_per_frame().div_ceil(self.cpu_divider())
+    }
+
+    pub fn frame_rate(self) -> f64 {
+        self.master_clock() as f64 / self.master_clocks_per_frame() as f64
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+
+// a new keyframe is taken after this many deltas
+pub const DELTAS_PER_KEYFRAME: usize = 30;
+
+pub const DEFAULT_INTERVAL: u32 = 5;
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+struct Group {
+    keyframe: Vec<u8>,
+    // (frame, compressed xor against the keyframe)
+    deltas: Vec<(u64, Vec<u8>)>,
+    frame: u64,
+    size: usize,
+}
+
+pub struct Rewind {
+    pub interval: u32,
+    pub budget: usize,
+    groups: VecDeque<Group>,
+    // decompressed keyframe of the newest group
+    base: Vec<u8>,
+    size: usize,
+}
+
+impl Rewind {
+    pub fn new(interval: u32, budget: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+            groups: VecDeque::new(),
+            base: vec![],
+            size: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|group| group.deltas.len() + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    // bytes currently held by compressed snapshots
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.base.clear();
+        self.size = 0;
+    }
+
+    pub fn push(&mut self, frame: u64, state: &[u8]) {
+        let needs_keyframe = match self.groups.back() {
+            Some(group) => {
+                group.deltas.len() >= DELTAS_PER_KEYFRAME
+                    || self.base.len() != state.len()
+            },
+            None => true,
+        };
+
+        if needs_keyframe {
+            let keyframe = encode(&[], state);
+            let size = keyframe.len();
+
+            self.base = state.to_vec();
+            self.groups.push_back(Group { keyframe, deltas: vec![], frame, size });
+            self.size += size;
+        } else {
+            let delta = encode(&self.base, state);
+            let group = self.groups.back_mut().unwrap();
+
+            group.size += delta.len();
+            self.size += delta.len();
+            group.deltas.push((frame, delta));
+        }
+
+        // always keep the newest group, even if it alone is over budget
+        while self.size > self.budget && self.groups.len() > 1 {
+            let group = self.groups.pop_front().unwrap();
+            self.size -= group.size;
+        }
+    }
+
+    // drops snapshots newer than `frame` and returns the newest one left.
+    // when `frame` is older than every snapshot there's nothing to return
+    // and nothing is dropped
+    pub fn seek(&mut self, frame: u64) -> Option<(u64, Vec<u8>)> {
+        if self.groups.front()?.frame > frame {
+            return None;
+        }
+
+        loop {
+            let group = self.groups.back_mut().unwrap();
+
+            match group.deltas.last() {
+                Some((delta_frame, _)) if *delta_frame > frame => {
+                    let (_, delta) = group.deltas.pop().unwrap();
+
+                    group.size -= delta.len();
+                    self.size -= delta.len();
+                },
+                Some((delta_frame, delta)) => {
+                    return Some((*delta_frame, decode(&self.base, delta)));
+                },
+                // there's an older group, the check above makes sure of it
+                None if group.frame > frame => {
+                    let group = self.groups.pop_back().unwrap();
+                    self.size -= group.size;
+
+                    // the previous group's keyframe becomes the base again
+                    let group = self.groups.back().unwrap();
+                    self.base = decode(&[], &group.keyframe);
+                },
+                None => return Some((group.frame, self.base.clone())),
+            }
+        }
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Rewind {
+        Rewind::new(DEFAULT_INTERVAL, DEFAULT_BUDGET)
+    }
+}
+
+// xors `state` against `base` and run-length encodes the zero runs, so
+// unchanged bytes cost almost nothing
+pub fn encode(base: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+
+    let byte = |i: usize| state[i] ^ base.get(i).copied().unwrap_or(0);
+
+    while i < state.len() {
+        let start = i;
+
+        while i < state.len() && byte(i) == 0 {
+            i += 1;
+        }
+
+        write_varint(&mut out, i - start);
+
+        let start = i;
+
+        // a literal run ends at the first pair of zero bytes
+        while i < state.len()
+            && (byte(i) != 0 || (i + 1 < state.len() && byte(i + 1) != 0))
+        {
+            i += 1;
+        }
+
+        write_varint(&mut out, i - start);
+        out.extend((start..i).map(byte));
+    }
+
+    out
+}
+
+pub fn decode(base: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state = vec![];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let zeros = read_varint(data, &mut pos);
+        let len = state.len();
+
+        state.extend((len..len + zeros).map(|i| base.get(i).copied().unwrap_or(0)));
+
+        let literals = read_varint(data, &mut pos);
+        let len = state.len();
+
+        state.extend(
+            data[pos..pos + literals].iter().enumerate()
+                .map(|(i, value)| value ^ base.get(len + i).copied().unwrap_or(0))
+        );
+
+        pos += literals;
+    }
+
+    state
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return value;
+        }
+    }
+}
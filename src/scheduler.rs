@@ -0,0 +1,118 @@
+use super::state::{Snapshot, StateReader, StateWriter, StateError};
+
+// something queued to happen at a master clock timestamp
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // copies a page to oam, stalling the cpu while it does
+    OamDma(u8),
+    // the apu frame counter's next step
+    FrameCounter,
+    // a device pulling the irq line or letting go of it, `source` is one
+    // of the `bus::IRQ_*` bits
+    Irq { source: u8, asserted: bool },
+    // brings a lagging ppu up to date, queued for where it next does
+    // something the cpu can't ask it about, like starting vblank
+    PpuSync,
+}
+
+// where the cpu is on the master clock, and the events queued after it.
+// components run at the timestamp their next cycle starts on, on a tie
+// the cpu goes first, then the ppu (whose clock the bus keeps), then
+// events in the order they were scheduled
+#[derive(Clone)]
+pub struct Scheduler {
+    pub cpu: u64,
+
+    // soonest last, so taking the next one is a pop
+    events: Vec<(u64, Event)>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler {
+            cpu: 0,
+            events: vec![],
+        }
+    }
+
+    pub fn schedule(&mut self, at: u64, event: Event) {
+        let i = self.events.partition_point(|(time, _)| *time > at);
+        self.events.insert(i, (at, event));
+    }
+
+    pub fn cancel(&mut self, event: Event) {
+        self.events.retain(|(_, queued)| *queued != event);
+    }
+
+    pub fn next_event(&self) -> Option<u64> {
+        self.events.last().map(|(time, _)| *time)
+    }
+
+    // the next event, if it's due before `end`
+    pub fn pop(&mut self, end: u64) -> Option<(u64, Event)> {
+        match self.next_event() {
+            Some(time) if time < end => self.events.pop(),
+            _ => None,
+        }
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &(u64, Event)> {
+        self.events.iter().rev()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
+
+impl Snapshot for Scheduler {
+    fn save(&self, w: &mut StateWriter) {
+        // syncs are worked out again on load, so a state doesn't depend on
+        // how the ppu was being run
+        let events: Vec<_> = self.events.iter().filter(|(_, event)| *event != Event::PpuSync).collect();
+
+        w.write_u64(self.cpu);
+        w.write_u32(events.len() as u32);
+
+        for (time, event) in events {
+            w.write_u64(*time);
+
+            match *event {
+                Event::OamDma(page) => {
+                    w.write_u8(0);
+                    w.write_u8(page);
+                },
+                Event::FrameCounter => w.write_u8(1),
+                Event::Irq { source, asserted } => {
+                    w.write_u8(2);
+                    w.write_u8(source);
+                    w.write_bool(asserted);
+                },
+                Event::PpuSync => unreachable!(),
+            }
+        }
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.cpu = r.read_u64()?;
+        self.events.clear();
+
+        for _ in 0..r.read_u32()? {
+            let time = r.read_u64()?;
+
+            let event = match r.read_u8()? {
+                0 => Event::OamDma(r.read_u8()?),
+                1 => Event::FrameCounter,
+                2 => Event::Irq { source: r.read_u8()?, asserted: r.read_bool()? },
+                _ => return Err(StateError::Invalid("event")),
+            };
+
+            // saved in order already
+            self.events.push((time, event));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use super::Nes;
+
+// saves live next to the rom, e.g. `zelda.nes` -> `zelda.sav`
+pub fn path_for_rom(rom: &Path) -> PathBuf {
+    rom.with_extension("sav")
+}
+
+impl Nes {
+    // returns false when there is no save file or the cartridge has no battery
+    pub fn load_sram_file(&mut self, path: &Path) -> io::Result<bool> {
+        if !self.has_battery() {
+            return Ok(false);
+        }
+
+        match fs::read(path) {
+            Ok(sram) => Ok(self.load_sram(&sram)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    // only touches the disk when the ram changed since the last save
+    pub fn save_sram_file(&mut self, path: &Path) -> io::Result<()> {
+        if !self.sram_dirty() {
+            return Ok(());
+        }
+
+        fs::write(path, self.sram())?;
+        self.mark_sram_saved();
+
+        Ok(())
+    }
+}
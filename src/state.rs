@@ -0,0 +1,202 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+use super::checksum::crc32;
+
+pub const MAGIC: [u8; 4] = *b"NESS";
+
+// bump whenever the layout of any snapshot changes
+pub const VERSION: u16 = 11;
+
+// magic + version + payload length + payload crc
+pub const HEADER_SIZE: usize = 4 + 2 + 4 + 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    IncompatibleVersion(u16),
+    BadChecksum,
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::IncompatibleVersion(version) => write!(
+                f, "save state version {} is not supported (expected {})",
+                version, VERSION,
+            ),
+            StateError::BadChecksum => write!(f, "save state checksum mismatch"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "save state has invalid {}", what),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<StateError> for JsValue {
+    fn from(error: StateError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+pub trait Snapshot {
+    fn save(&self, w: &mut StateWriter);
+    fn load(&mut self, r: &mut StateReader) -> Result<(), StateError>;
+}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter { data: vec![] }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+
+    // wraps the payload in a header so it can be validated on load
+    pub fn finish(self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(HEADER_SIZE + self.data.len());
+
+        state.extend_from_slice(&MAGIC);
+        state.extend_from_slice(&VERSION.to_le_bytes());
+        state.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        state.extend_from_slice(&crc32(&self.data).to_le_bytes());
+        state.extend_from_slice(&self.data);
+
+        state
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> StateWriter {
+        StateWriter::new()
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    // checks the header and checksum before any state is touched
+    pub fn new(state: &'a [u8]) -> Result<StateReader<'a>, StateError> {
+        if state.len() < HEADER_SIZE {
+            return Err(StateError::Truncated);
+        }
+
+        if state[0..4] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = u16::from_le_bytes([state[4], state[5]]);
+
+        if version != VERSION {
+            return Err(StateError::IncompatibleVersion(version));
+        }
+
+        let len = u32::from_le_bytes([state[6], state[7], state[8], state[9]]) as usize;
+        let crc = u32::from_le_bytes([state[10], state[11], state[12], state[13]]);
+        let data = &state[HEADER_SIZE..];
+
+        if data.len() != len {
+            return Err(StateError::Truncated);
+        }
+
+        if crc32(data) != crc {
+            return Err(StateError::BadChecksum);
+        }
+
+        Ok(StateReader { data, pos: 0 })
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        // a corrupt length can be anything, it mustn't wrap around
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len()).ok_or(StateError::Truncated)?;
+
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+
+        Ok(bytes)
+    }
+
+    // after the last field, anything left over means the state doesn't
+    // have the layout it claims to
+    pub fn finish(&self) -> Result<(), StateError> {
+        if self.pos != self.data.len() {
+            return Err(StateError::Invalid("trailing data"));
+        }
+
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], StateError> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+
+    // fixed size buffers must come back with the same size
+    pub fn read_into(&mut self, buf: &mut [u8]) -> Result<(), StateError> {
+        let bytes = self.read_bytes()?;
+
+        if bytes.len() != buf.len() {
+            return Err(StateError::Invalid("buffer size"));
+        }
+
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
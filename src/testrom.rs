@@ -0,0 +1,118 @@
+use super::{Nes, inspect::MemorySpace};
+
+// blargg's test roms report through prg-ram once these bytes are at $6001
+pub const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+
+pub const STATUS_ADDR: usize = 0x6000;
+pub const SIGNATURE_ADDR: usize = 0x6001;
+pub const MESSAGE_ADDR: usize = 0x6004;
+
+pub const STATUS_RUNNING: u8 = 0x80;
+pub const STATUS_NEEDS_RESET: u8 = 0x81;
+
+pub const DEFAULT_FRAME_BUDGET: u64 = 60 * 60;
+
+// roms asking for a reset want it at least 100ms later
+pub const RESET_DELAY_FRAMES: u64 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestStatus {
+    Passed,
+    Failed(u8),
+    // the rom never finished within the frame budget
+    Timeout,
+    // the cpu stopped on an opcode it can't run, see `Cpu::halted`
+    Halted { pc: u16, opcode: u8 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestResult {
+    pub status: TestStatus,
+    pub message: String,
+    pub frames: u64,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.status == TestStatus::Passed
+    }
+}
+
+pub struct TestRomRunner {
+    pub frame_budget: u64,
+}
+
+impl TestRomRunner {
+    pub fn new(frame_budget: u64) -> TestRomRunner {
+        TestRomRunner { frame_budget }
+    }
+
+    // runs the rom already loaded into `nes` until it reports a result
+    pub fn run(&self, nes: &mut Nes) -> TestResult {
+        let mut frames = 0;
+        let mut reset_at = None;
+
+        let status = loop {
+            if frames >= self.frame_budget {
+                break TestStatus::Timeout;
+            }
+
+            nes.tick_frame();
+            frames += 1;
+
+            let cpu = nes.cpu();
+
+            if let Some(opcode) = cpu.halted {
+                break TestStatus::Halted { pc: cpu.pc, opcode };
+            }
+
+            if !has_signature(nes) {
+                continue;
+            }
+
+            match peek(nes, STATUS_ADDR) {
+                STATUS_RUNNING => {},
+                STATUS_NEEDS_RESET => {
+                    let at = *reset_at.get_or_insert(frames + RESET_DELAY_FRAMES);
+
+                    if frames >= at {
+                        nes.reset();
+                        reset_at = None;
+                    }
+                },
+                0 => break TestStatus::Passed,
+                code => break TestStatus::Failed(code),
+            }
+        };
+
+        TestResult { status, message: read_message(nes), frames }
+    }
+}
+
+impl Default for TestRomRunner {
+    fn default() -> TestRomRunner {
+        TestRomRunner::new(DEFAULT_FRAME_BUDGET)
+    }
+}
+
+// the rom's report is read without disturbing anything on the bus
+fn peek(nes: &Nes, addr: usize) -> u8 {
+    nes.peek(MemorySpace::CpuBus, addr).unwrap_or(0)
+}
+
+fn has_signature(nes: &Nes) -> bool {
+    SIGNATURE.iter().enumerate().all(|(i, byte)| peek(nes, SIGNATURE_ADDR + i) == *byte)
+}
+
+fn read_message(nes: &Nes) -> String {
+    if !has_signature(nes) {
+        return String::new();
+    }
+
+    let bytes: Vec<u8> = (MESSAGE_ADDR..0x8000)
+        .map(|addr| peek(nes, addr))
+        .take_while(|byte| *byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
+}
@@ -0,0 +1,200 @@
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use super::{
+    cpu::Cpu,
+    bus::{Bus, CpuBus},
+    disasm,
+    opcodes::Mode,
+};
+
+pub const DEFAULT_BUFFER_LINES: usize = 10_000;
+
+// one executed instruction, captured right before it runs
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub bytes: Vec<u8>,
+    pub name: &'static str,
+    pub operand: String,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub scanline: u16,
+    pub dot: u16,
+    pub cycles: u64,
+}
+
+impl TraceEntry {
+    pub fn capture(cpu: &Cpu, bus: &Bus) -> TraceEntry {
+        let read = |addr: usize| bus.peek(addr & 0xffff);
+        let read_word = |lo: usize, hi: usize| (read(lo) as u16) | ((read(hi) as u16) << 8);
+
+        let line = disasm::decode(|addr| read(addr as usize), cpu.pc);
+        let name = line.name.unwrap_or("???");
+        let zp = line.operand as usize & 0xff;
+        let abs = line.operand as usize;
+
+        let operand = match line.mode {
+            Mode::Implied => String::new(),
+            Mode::Accumulator => "A".to_string(),
+            Mode::Immediate => format!("#${:02X}", zp),
+            Mode::ZeroPage => format!("${:02X} = {:02X}", zp, read(zp)),
+            Mode::ZeroPageX => {
+                let addr = (zp + cpu.x as usize) & 0xff;
+                format!("${:02X},X @ {:02X} = {:02X}", zp, addr, read(addr))
+            },
+            Mode::ZeroPageY => {
+                let addr = (zp + cpu.y as usize) & 0xff;
+                format!("${:02X},Y @ {:02X} = {:02X}", zp, addr, read(addr))
+            },
+            Mode::Relative => format!("${:04X}", line.operand),
+            // jumps show the target, not the byte stored there
+            Mode::Absolute if name == "JMP" || name == "JSR" => format!("${:04X}", abs),
+            Mode::Absolute => format!("${:04X} = {:02X}", abs, read(abs)),
+            Mode::AbsoluteX => {
+                let addr = (abs + cpu.x as usize) & 0xffff;
+                format!("${:04X},X @ {:04X} = {:02X}", abs, addr, read(addr))
+            },
+            Mode::AbsoluteY => {
+                let addr = (abs + cpu.y as usize) & 0xffff;
+                format!("${:04X},Y @ {:04X} = {:02X}", abs, addr, read(addr))
+            },
+            Mode::Indirect => {
+                // the high byte never leaves the pointer's page
+                let target = read_word(abs, (abs & 0xff00) | ((abs + 1) & 0x00ff));
+                format!("(${:04X}) = {:04X}", abs, target)
+            },
+            Mode::IndirectX => {
+                let ptr = (zp + cpu.x as usize) & 0xff;
+                let addr = read_word(ptr, (ptr + 1) & 0xff) as usize;
+                format!("(${:02X},X) @ {:02X} = {:04X} = {:02X}", zp, ptr, addr, read(addr))
+            },
+            Mode::IndirectY => {
+                let base = read_word(zp, (zp + 1) & 0xff) as usize;
+                let addr = (base + cpu.y as usize) & 0xffff;
+                format!("(${:02X}),Y = {:04X} @ {:04X} = {:02X}", zp, base, addr, read(addr))
+            },
+        };
+
+        TraceEntry {
+            pc: cpu.pc,
+            bytes: line.bytes,
+            name,
+            operand,
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            p: cpu.p,
+            sp: cpu.sp,
+            scanline: bus.ppu.scanline,
+            dot: bus.ppu.dot,
+            cycles: cpu.cycles,
+        }
+    }
+}
+
+// formats the entry exactly like a line of nestest.log
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let disassembly = format!("{} {}", self.name, self.operand);
+
+        let instruction = format!(
+            "{:04X}  {:8}  {}",
+            self.pc, bytes.join(" "), disassembly.trim_end(),
+        );
+
+        write!(
+            f, "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:3},{:3} CYC:{}",
+            instruction, self.a, self.x, self.y, self.p, self.sp,
+            self.scanline, self.dot, self.cycles,
+        )
+    }
+}
+
+pub trait TraceSink {
+    fn trace(&mut self, entry: &TraceEntry);
+
+    // pushes out anything held back and reports what went wrong since the
+    // last flush, sinks that don't buffer or fail have nothing to do
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// in memory sink for the wasm api, keeps the newest `limit` lines
+#[derive(Clone)]
+pub struct TraceBuffer {
+    pub limit: usize,
+    lines: Rc<RefCell<VecDeque<String>>>,
+}
+
+impl TraceBuffer {
+    pub fn new(limit: usize) -> TraceBuffer {
+        TraceBuffer {
+            limit,
+            lines: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn take(&self) -> Vec<String> {
+        self.lines.borrow_mut().drain(..).collect()
+    }
+}
+
+impl Default for TraceBuffer {
+    fn default() -> TraceBuffer {
+        TraceBuffer::new(DEFAULT_BUFFER_LINES)
+    }
+}
+
+impl TraceSink for TraceBuffer {
+    fn trace(&mut self, entry: &TraceEntry) {
+        let mut lines = self.lines.borrow_mut();
+
+        if lines.len() >= self.limit {
+            lines.pop_front();
+        }
+
+        lines.push_back(entry.to_string());
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct WriteSink<W: io::Write> {
+    writer: W,
+    // the first write that failed, nothing more is written after it
+    error: Option<io::Error>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<W: io::Write> WriteSink<W> {
+    pub fn new(writer: W) -> WriteSink<W> {
+        WriteSink { writer, error: None }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<W: io::Write> TraceSink for WriteSink<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        // a broken sink shouldn't take the emulator down with it, the
+        // error waits for `flush`
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.writer, "{}", entry) {
+                self.error = Some(error);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use wasm_bindgen::prelude::*;
+use super::{
+    palette,
+    bus::Bus,
+    ppu::{self, Ppu},
+};
+
+// every view is a row major rgba buffer of a fixed size
+pub const PATTERN_TABLE_WIDTH: usize = 128;
+pub const PATTERN_TABLE_HEIGHT: usize = 128;
+pub const NAMETABLES_WIDTH: usize = 512;
+pub const NAMETABLES_HEIGHT: usize = 480;
+// 8x8 grid of cells tall enough for 8x16 sprites
+pub const SPRITE_SHEET_WIDTH: usize = 64;
+pub const SPRITE_SHEET_HEIGHT: usize = 128;
+// background palettes on the top row, sprite palettes on the bottom
+pub const PALETTE_WIDTH: usize = 16;
+pub const PALETTE_HEIGHT: usize = 2;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+// outline of the visible screen drawn over the nametables
+const SCROLL_OVERLAY: [u8; 4] = [0xff, 0x00, 0xff, 0xff];
+const TRANSPARENT: [u8; 4] = [0; 4];
+
+// one oam entry with its attribute byte decoded
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    pub index: u8,
+    pub x: u8,
+    // as stored, the sprite shows up a line below this
+    pub y: u8,
+    pub tile: u8,
+    // 0-3, i.e. palettes 4-7 of palette ram
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+struct Image {
+    width: usize,
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: usize, height: usize) -> Image {
+        Image { width, data: vec![0; width * height * 4] }
+    }
+
+    fn set(&mut self, x: usize, y: usize, rgba: [u8; 4]) {
+        let i = (y * self.width + x) * 4;
+        self.data[i..i + 4].copy_from_slice(&rgba);
+    }
+}
+
+// 2 bit colour of a pixel in a tile, 0 being transparent
+fn tile_pixel(bus: &Bus, table: u16, tile: u8, x: usize, y: usize) -> u8 {
+    let addr = table * 0x1000 + tile as u16 * 16 + y as u16;
+    let bit = 7 - x;
+
+    let lo = (bus.read_vram(addr) >> bit) & 1;
+    let hi = (bus.read_vram(addr + 8) >> bit) & 1;
+
+    (hi << 1) | lo
+}
+
+// `palette` 0-3 are background palettes, 4-7 sprite palettes
+fn color(bus: &Bus, palette: usize, pixel: u8) -> [u8; 4] {
+    let addr = if pixel == 0 {
+        0x3f00
+    } else {
+        0x3f00 + (palette as u16 & 7) * 4 + pixel as u16
+    };
+
+    palette::rgba(bus.read_vram(addr))
+}
+
+// both halves of chr as 16x16 tiles each, `table` 0 or 1
+pub fn pattern_table(bus: &Bus, table: usize, palette: usize) -> Vec<u8> {
+    let mut image = Image::new(PATTERN_TABLE_WIDTH, PATTERN_TABLE_HEIGHT);
+
+    for tile in 0..=255u8 {
+        let (tx, ty) = (tile as usize % 16 * 8, tile as usize / 16 * 8);
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let pixel = tile_pixel(bus, table as u16 & 1, tile, x, y);
+                image.set(tx + x, ty + y, color(bus, palette, pixel));
+            }
+        }
+    }
+
+    image.data
+}
+
+// where the screen's top left corner is within the 4 nametables, t holds
+// the scroll the game set up for the frame
+fn scroll(ppu: &Ppu) -> (usize, usize) {
+    let t = ppu.t as usize;
+    let scroll_x = (t >> 10 & 1) * SCREEN_WIDTH + (t & 0x1f) * 8 + ppu.x as usize;
+    let scroll_y = (t >> 11 & 1) * SCREEN_HEIGHT + (t >> 5 & 0x1f) * 8 + (t >> 12 & 7);
+
+    (scroll_x, scroll_y)
+}
+
+// 2 bit colour and palette of the background at a point in the 4
+// nametables
+fn background_pixel(bus: &Bus, table: u16, x: usize, y: usize) -> (u8, usize) {
+    let nametable = x / SCREEN_WIDTH + y / SCREEN_HEIGHT * 2;
+    let base = 0x2000 + nametable as u16 * 0x400;
+    let (x, y) = (x % SCREEN_WIDTH, y % SCREEN_HEIGHT);
+    let (row, col) = (y / 8, x / 8);
+
+    let tile = bus.read_vram(base + (row * 32 + col) as u16);
+    let attribute = bus.read_vram(base + 0x3c0 + (row / 4 * 8 + col / 4) as u16);
+    let palette = (attribute >> (((row & 2) << 1) | (col & 2))) & 3;
+
+    (tile_pixel(bus, table, tile, x % 8, y % 8), palette as usize)
+}
+
+// the screen as the ppu would draw it if nothing changed mid frame. the
+// ppu doesn't output pixels itself yet, so this composes the background
+// at the frame's scroll with the sprites in oam
+pub fn screen(bus: &Bus) -> Vec<u8> {
+    let ppu = &bus.ppu;
+    let mut image = Image::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut opaque = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+    let table = (ppu.ctrl & ppu::CTRL_BACKGROUND_TABLE != 0) as u16;
+    let (scroll_x, scroll_y) = scroll(ppu);
+    let backdrop = color(bus, 0, 0);
+
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let rgba = if ppu.mask & ppu::MASK_BACKGROUND != 0 {
+                let nx = (scroll_x + x) % NAMETABLES_WIDTH;
+                let ny = (scroll_y + y) % NAMETABLES_HEIGHT;
+                let (pixel, palette) = background_pixel(bus, table, nx, ny);
+
+                opaque[y * SCREEN_WIDTH + x] = pixel != 0;
+                color(bus, palette, pixel)
+            } else {
+                backdrop
+            };
+
+            image.set(x, y, rgba);
+        }
+    }
+
+    if ppu.mask & ppu::MASK_SPRITES == 0 {
+        return image.data;
+    }
+
+    let tall = ppu.ctrl & ppu::CTRL_SPRITE_SIZE != 0;
+    let height = if tall { 16 } else { 8 };
+
+    // lower oam entries are drawn last so they end up in front
+    for sprite in sprites(ppu).iter().rev() {
+        let (table, tile) = if tall {
+            ((sprite.tile & 1) as u16, sprite.tile & 0xfe)
+        } else {
+            ((ppu.ctrl & ppu::CTRL_SPRITE_TABLE != 0) as u16, sprite.tile)
+        };
+
+        for y in 0..height {
+            for x in 0..8 {
+                let (sx, sy) = (sprite.x as usize + x, sprite.y as usize + 1 + y);
+
+                if sx >= SCREEN_WIDTH || sy >= SCREEN_HEIGHT {
+                    continue;
+                }
+
+                let fx = if sprite.flip_horizontal { 7 - x } else { x };
+                let fy = if sprite.flip_vertical { height - 1 - y } else { y };
+                let pixel = tile_pixel(bus, table, tile.wrapping_add((fy / 8) as u8), fx, fy % 8);
+
+                if pixel == 0 || (sprite.behind_background && opaque[sy * SCREEN_WIDTH + sx]) {
+                    continue;
+                }
+
+                image.set(sx, sy, color(bus, 4 + sprite.palette as usize, pixel));
+            }
+        }
+    }
+
+    image.data
+}
+
+// the 4 logical nametables as laid out in the address space, with the
+// screen the scroll registers point at outlined
+pub fn nametables(bus: &Bus) -> Vec<u8> {
+    let ppu = &bus.ppu;
+    let mut image = Image::new(NAMETABLES_WIDTH, NAMETABLES_HEIGHT);
+    let table = (ppu.ctrl & ppu::CTRL_BACKGROUND_TABLE != 0) as u16;
+
+    for nametable in 0..4 {
+        let base = 0x2000 + nametable as u16 * 0x400;
+        let (ox, oy) = (nametable % 2 * SCREEN_WIDTH, nametable / 2 * SCREEN_HEIGHT);
+
+        for row in 0..30 {
+            for col in 0..32 {
+                let tile = bus.read_vram(base + (row * 32 + col) as u16);
+                let attribute = bus.read_vram(base + 0x3c0 + (row / 4 * 8 + col / 4) as u16);
+                let palette = (attribute >> (((row & 2) << 1) | (col & 2))) & 3;
+
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let pixel = tile_pixel(bus, table, tile, x, y);
+                        let rgba = color(bus, palette as usize, pixel);
+
+                        image.set(ox + col * 8 + x, oy + row * 8 + y, rgba);
+                    }
+                }
+            }
+        }
+    }
+
+    let (scroll_x, scroll_y) = scroll(ppu);
+
+    for i in 0..SCREEN_WIDTH {
+        let x = (scroll_x + i) % NAMETABLES_WIDTH;
+
+        image.set(x, scroll_y % NAMETABLES_HEIGHT, SCROLL_OVERLAY);
+        image.set(x, (scroll_y + SCREEN_HEIGHT - 1) % NAMETABLES_HEIGHT, SCROLL_OVERLAY);
+    }
+
+    for i in 0..SCREEN_HEIGHT {
+        let y = (scroll_y + i) % NAMETABLES_HEIGHT;
+
+        image.set(scroll_x % NAMETABLES_WIDTH, y, SCROLL_OVERLAY);
+        image.set((scroll_x + SCREEN_WIDTH - 1) % NAMETABLES_WIDTH, y, SCROLL_OVERLAY);
+    }
+
+    image.data
+}
+
+pub fn sprites(ppu: &Ppu) -> Vec<Sprite> {
+    ppu.oam.chunks(4).enumerate().map(|(index, entry)| {
+        let attributes = entry[2];
+
+        Sprite {
+            index: index as u8,
+            y: entry[0],
+            tile: entry[1],
+            x: entry[3],
+            palette: attributes & 3,
+            behind_background: attributes & 0b00100000 != 0,
+            flip_horizontal: attributes & 0b01000000 != 0,
+            flip_vertical: attributes & 0b10000000 != 0,
+        }
+    }).collect()
+}
+
+// every sprite in oam order, 8 to a row, unused pixels are transparent
+pub fn sprite_sheet(bus: &Bus) -> Vec<u8> {
+    let ppu = &bus.ppu;
+    let mut image = Image::new(SPRITE_SHEET_WIDTH, SPRITE_SHEET_HEIGHT);
+    let tall = ppu.ctrl & ppu::CTRL_SPRITE_SIZE != 0;
+    let height = if tall { 16 } else { 8 };
+
+    for sprite in sprites(ppu) {
+        let (ox, oy) = (sprite.index as usize % 8 * 8, sprite.index as usize / 8 * 16);
+
+        // 8x16 sprites pick their table with bit 0 of the tile
+        let (table, tile) = if tall {
+            ((sprite.tile & 1) as u16, sprite.tile & 0xfe)
+        } else {
+            ((ppu.ctrl & ppu::CTRL_SPRITE_TABLE != 0) as u16, sprite.tile)
+        };
+
+        for y in 0..height {
+            for x in 0..8 {
+                let sx = if sprite.flip_horizontal { 7 - x } else { x };
+                let sy = if sprite.flip_vertical { height - 1 - y } else { y };
+
+                let pixel = tile_pixel(bus, table, tile.wrapping_add((sy / 8) as u8), sx, sy % 8);
+
+                let rgba = match pixel {
+                    0 => TRANSPARENT,
+                    _ => color(bus, 4 + sprite.palette as usize, pixel),
+                };
+
+                image.set(ox + x, oy + y, rgba);
+            }
+        }
+    }
+
+    image.data
+}
+
+// palette ram as it reads back, mirrored entries included
+pub fn palette_ram(bus: &Bus) -> Vec<u8> {
+    let mut image = Image::new(PALETTE_WIDTH, PALETTE_HEIGHT);
+
+    for i in 0..ppu::PALETTE_SIZE {
+        let color = bus.read_vram(0x3f00 + i as u16);
+        image.set(i % PALETTE_WIDTH, i / PALETTE_WIDTH, palette::rgba(color));
+    }
+
+    image.data
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl super::Nes {
+    // writes every view into `dir` as pngs, pattern tables use palette 0
+    pub fn save_ppu_views(&self, dir: &std::path::Path) -> std::io::Result<()> {
+        use super::png;
+
+        let bus = self.bus();
+
+        let views = [
+            ("pattern0.png", PATTERN_TABLE_WIDTH, PATTERN_TABLE_HEIGHT, pattern_table(bus, 0, 0)),
+            ("pattern1.png", PATTERN_TABLE_WIDTH, PATTERN_TABLE_HEIGHT, pattern_table(bus, 1, 0)),
+            ("nametables.png", NAMETABLES_WIDTH, NAMETABLES_HEIGHT, nametables(bus)),
+            ("sprites.png", SPRITE_SHEET_WIDTH, SPRITE_SHEET_HEIGHT, sprite_sheet(bus)),
+            ("palette.png", PALETTE_WIDTH, PALETTE_HEIGHT, palette_ram(bus)),
+        ];
+
+        for (name, width, height, rgba) in views.iter() {
+            png::write(&dir.join(name), *width as u32, *height as u32, rgba)?;
+        }
+
+        Ok(())
+    }
+}
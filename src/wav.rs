@@ -0,0 +1,64 @@
+use wasm_bindgen::prelude::*;
+
+// how samples are stored, 16 bit is what most tools expect, float keeps
+// the mixer's output exactly
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SampleFormat {
+    #[default]
+    Pcm16,
+    Float32,
+}
+
+impl SampleFormat {
+    fn bits(self) -> u16 {
+        match self {
+            SampleFormat::Pcm16 => 16,
+            SampleFormat::Float32 => 32,
+        }
+    }
+
+    // the fmt chunk's format tag
+    fn tag(self) -> u16 {
+        match self {
+            SampleFormat::Pcm16 => 1,
+            SampleFormat::Float32 => 3,
+        }
+    }
+}
+
+// a mono riff wave file of samples in -1.0..=1.0, louder ones are clipped
+pub fn encode(samples: &[f32], sample_rate: u32, format: SampleFormat) -> Vec<u8> {
+    let block = format.bits() as u32 / 8;
+    let data_size = samples.len() as u32 * block;
+
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&format.tag().to_le_bytes());
+    // one channel
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block).to_le_bytes());
+    wav.extend_from_slice(&(block as u16).to_le_bytes());
+    wav.extend_from_slice(&format.bits().to_le_bytes());
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+
+    for sample in samples {
+        let sample = sample.clamp(-1.0, 1.0);
+
+        match format {
+            SampleFormat::Pcm16 => wav.extend_from_slice(&((sample * 32767.0).round() as i16).to_le_bytes()),
+            SampleFormat::Float32 => wav.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+
+    wav
+}
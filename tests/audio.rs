@@ -0,0 +1,133 @@
+use nes::Nes;
+use nes::audio::{self, CHANNELS};
+use nes::wav::{self, SampleFormat};
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+fn u16_at(bytes: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([bytes[i], bytes[i + 1]])
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+#[test]
+fn wav_headers() {
+    let samples = [0.0, 0.5, -1.0, 2.0];
+
+    let pcm = wav::encode(&samples, 44_100, SampleFormat::Pcm16);
+    assert_eq!(&pcm[..4], b"RIFF");
+    assert_eq!(u32_at(&pcm, 4) as usize, pcm.len() - 8);
+    assert_eq!(&pcm[8..16], b"WAVEfmt ");
+    assert_eq!((u16_at(&pcm, 20), u16_at(&pcm, 22)), (1, 1));
+    assert_eq!((u32_at(&pcm, 24), u32_at(&pcm, 28)), (44_100, 88_200));
+    assert_eq!((u16_at(&pcm, 32), u16_at(&pcm, 34)), (2, 16));
+    assert_eq!(&pcm[36..40], b"data");
+    assert_eq!(u32_at(&pcm, 40), 8);
+
+    // out of range samples are clipped
+    let values: Vec<i16> = pcm[44..].chunks(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+    assert_eq!(values, [0, 16384, -32767, 32767]);
+
+    let float = wav::encode(&samples, 48_000, SampleFormat::Float32);
+    assert_eq!((u16_at(&float, 20), u16_at(&float, 34)), (3, 32));
+    assert_eq!(u32_at(&float, 28), 192_000);
+    assert_eq!(u32_at(&float, 40), 16);
+    assert_eq!(f32::from_le_bytes([float[48], float[49], float[50], float[51]]), 0.5);
+}
+
+#[test]
+fn mixer() {
+    assert_eq!(audio::mix([0; 5]), 0.0);
+
+    // the pulses share a curve, so two at once aren't twice as loud
+    let one = audio::mix([15, 0, 0, 0, 0]);
+    assert_eq!(one, audio::mix([0, 15, 0, 0, 0]));
+    assert!(audio::mix([15, 15, 0, 0, 0]) < 2.0 * one);
+
+    // everything at full tops out around 1
+    assert!((audio::mix([15, 15, 15, 15, 127]) - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn capture_follows_the_master_clock() {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+
+    // nothing recorded until asked
+    assert!(nes.audio_wav(SampleFormat::Pcm16).is_empty());
+
+    let start = nes.machine().master_clock();
+    nes.start_audio_capture(44_100, true);
+
+    for _ in 0..60 {
+        nes.tick_frame();
+    }
+
+    let elapsed = nes.machine().master_clock() - start;
+    let expected = (elapsed * 44_100 / nes.region().master_clock()) as usize;
+
+    let capture = nes.audio_capture().unwrap();
+    assert_eq!(capture.mix.len(), expected);
+
+    // the apu has no channels yet, see `audio::levels`, so it's silence
+    assert!(capture.mix.iter().all(|sample| *sample == 0.0));
+
+    let wav = nes.audio_wav(SampleFormat::Pcm16);
+    assert_eq!(wav.len(), 44 + 2 * expected);
+
+    for channel in 0..CHANNELS.len() {
+        let stem = nes.audio_stem_wav(channel, SampleFormat::Float32).unwrap();
+        assert_eq!(stem.len(), 44 + 4 * expected);
+    }
+
+    assert!(nes.audio_stem_wav(CHANNELS.len(), SampleFormat::Pcm16).is_none());
+
+    // going back to an earlier state drops what was recorded after it
+    let state = nes.save_state();
+
+    for _ in 0..3 {
+        nes.tick_frame();
+    }
+
+    assert!(nes.audio_capture().unwrap().mix.len() > expected);
+
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.audio_capture().unwrap().mix.len(), expected);
+    assert!(nes.audio_capture().unwrap().stems.as_ref().unwrap().iter().all(|stem| stem.len() == expected));
+
+    // and records the same again from there
+    nes.tick_frame();
+
+    let elapsed = nes.machine().master_clock() - start;
+    let length = nes.audio_capture().unwrap().mix.len();
+    assert_eq!(length, (elapsed * 44_100 / nes.region().master_clock()) as usize);
+
+    nes.stop_audio_capture();
+    assert!(nes.audio_stem_wav(0, SampleFormat::Pcm16).is_none());
+}
+
+#[test]
+fn loading_from_before_the_capture_starts_over() {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+
+    nes.tick_frame();
+    let state = nes.save_state();
+    nes.tick_frame();
+
+    nes.start_audio_capture(48_000, false);
+    nes.tick_frame();
+    nes.load_state(&state).unwrap();
+
+    assert!(nes.audio_capture().unwrap().mix.is_empty());
+
+    let start = nes.machine().master_clock();
+    nes.tick_frame();
+
+    let elapsed = nes.machine().master_clock() - start;
+    assert_eq!(nes.audio_capture().unwrap().mix.len(), (elapsed * 48_000 / nes.region().master_clock()) as usize);
+}
@@ -0,0 +1,178 @@
+use nes::bus::{Bus, BusRead, BusWrite, CpuBus, APU_STATUS};
+use nes::cartridge::Cartridge;
+use nes::input::{BUTTON_A, BUTTON_START};
+use nes::machine::Machine;
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+fn assert_send<T: Send + Clone>() {}
+
+// a register at $5000 counting the reads of it
+#[derive(Clone, Default)]
+struct Counter {
+    reads: u8,
+}
+
+impl BusRead for Counter {
+    fn read(&mut self, addr: usize) -> Option<u8> {
+        let value = self.peek(addr)?;
+        self.reads += 1;
+        Some(value)
+    }
+
+    fn peek(&self, addr: usize) -> Option<u8> {
+        if addr == 0x5000 { Some(self.reads) } else { None }
+    }
+}
+
+impl BusWrite for Counter {
+    fn write(&mut self, addr: usize, value: u8) -> bool {
+        if addr == 0x5000 {
+            self.reads = value;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[test]
+fn devices_share_a_page() {
+    let mut bus = Bus::new();
+    bus.insert(Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap());
+    bus.input.buttons[0] = BUTTON_A | BUTTON_START;
+
+    bus.write(0x4016, 1);
+    bus.write(0x4016, 0);
+
+    // the controllers answer, the rest of the page is left to the cartridge
+    assert_eq!(bus.peek(0x4016), 1);
+    assert_eq!(bus.read(0x4016), 1);
+    assert_eq!(bus.read(0x4016), 0);
+    assert_eq!(bus.read(0x4017), 0);
+    assert_eq!(bus.read(0x4020), 0);
+}
+
+#[test]
+fn fast_paths_mirror() {
+    let mut bus = Bus::new();
+    bus.insert(Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap());
+
+    bus.write(0x0805, 0x42);
+    assert_eq!(bus.ram.data[5], 0x42);
+    assert_eq!(bus.read(0x1805), 0x42);
+
+    // 16k of prg rom shows up at $8000 and $c000
+    assert_eq!(bus.read(0x8000), IDLE_LOOP[0]);
+    assert_eq!(bus.read(0xc001), IDLE_LOOP[1]);
+    assert_eq!(bus.read(0xfffd), 0xc0);
+
+    bus.write(0x6010, 0x33);
+    assert_eq!(bus.read(0x6010), 0x33);
+
+    // ejecting takes the cartridge off every page, leaving open bus
+    bus.eject();
+    bus.read(0x0805);
+    assert_eq!(bus.read(0x6010), 0x42);
+    assert_eq!(bus.read(0xc000), 0x42);
+}
+
+#[test]
+fn accesses_are_logged_on_request() {
+    let mut bus = Bus::new();
+
+    bus.write(0x0010, 7);
+    assert!(bus.log.is_none());
+
+    bus.log = Some(vec![]);
+    bus.write(0x0010, 8);
+    bus.read(0x0010);
+    bus.peek(0x0010);
+
+    let log = bus.log.unwrap();
+    assert_eq!(log.len(), 2);
+    assert!(log[0].write && !log[1].write);
+    assert_eq!((log[1].addr, log[1].value), (0x0010, 8));
+}
+
+#[test]
+fn machine_is_owned() {
+    assert_send::<Machine>();
+
+    let mut machine = Machine::new();
+    machine.bus.insert(Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap());
+    machine.reset();
+
+    let saved = machine.clone();
+
+    for _ in 0..1000 {
+        machine.clock();
+    }
+
+    // the clone shares nothing with what kept running
+    assert_eq!(saved.cpu.cycles, 0);
+    assert!(machine.cpu.cycles > 0);
+    assert_eq!(saved.bus.ppu.scanline, 0);
+}
+
+#[test]
+fn unmapped_reads_return_open_bus() {
+    let mut bus = Bus::new();
+    bus.insert(Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap());
+    bus.input.buttons[0] = BUTTON_A;
+
+    bus.write(0x0000, 0x40);
+    bus.read(0x0000);
+    assert_eq!(bus.read(0x5000), 0x40);
+
+    bus.write(0x0010, 0x5a);
+    assert_eq!(bus.peek(0x4020), 0x5a);
+
+    // only the low bits of the controller ports are driven, like after
+    // LDA $4016 where the last thing on the bus was the address's $40
+    bus.write(0x4016, 1);
+    bus.write(0x4016, 0);
+    bus.read(0x0000);
+    assert_eq!(bus.read(0x4016), 0x41);
+    assert_eq!(bus.read(0x4016), 0x40);
+
+    // and $4015 doesn't put its value on the bus
+    bus.write(0x0000, 0xff);
+    assert_eq!(bus.read(APU_STATUS), 0x20);
+    assert_eq!(bus.read(0x5000), 0xff);
+}
+
+#[test]
+fn devices_can_be_mapped() {
+    let mut machine = Machine::new();
+    machine.bus.insert(Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap());
+
+    let index = machine.bus.map(0x5000..=0x5000, Box::new(Counter::default()));
+    let bus = &mut machine.bus;
+
+    assert_eq!(bus.read(0x5000), 0);
+    assert_eq!(bus.read(0x5000), 1);
+    assert_eq!(bus.peek(0x5000), 2);
+
+    bus.write(0x5000, 0x10);
+    assert_eq!(bus.device(index).unwrap().peek(0x5000), Some(0x10));
+
+    // the rest of the page is the device's too, what it turns down is open bus
+    bus.write(0x6000, 0x77);
+    bus.write(0x0000, 0x33);
+    assert_eq!(bus.read(0x50ff), 0x33);
+    assert_eq!(bus.read(0x6000), 0x77);
+
+    // each clone of the machine gets its own
+    let mut saved = machine.clone();
+    machine.bus.read(0x5000);
+
+    assert_eq!(saved.bus.read(0x5000), 0x10);
+    assert_eq!(machine.bus.peek(0x5000), 0x11);
+
+    // and it outlives swapping the cartridge
+    machine.bus.eject();
+    assert_eq!(machine.bus.peek(0x5000), 0x11);
+}
@@ -0,0 +1,192 @@
+use std::env;
+use std::fs;
+use nes::Nes;
+use nes::cartridge::{Cartridge, CartridgeError, Mirroring, CHR_BANK_SIZE, HEADER_SIZE, PRG_BANK_SIZE, PRG_RAM_SIZE, TRAINER_SIZE};
+use nes::region::Region;
+use nes::sram;
+
+mod common;
+
+use common::{chr_ram_rom, nrom_with_program, IDLE_LOOP};
+
+// ASL $6000; CLC; BCC -2, shifting the first byte of prg ram once
+const SHIFT_SRAM: [u8; 6] = [0x0e, 0x00, 0x60, 0x18, 0x90, 0xfd];
+
+fn battery_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = nrom_with_program(program);
+    rom[6] |= 0b00000010;
+    rom
+}
+
+fn error(rom: &[u8]) -> CartridgeError {
+    Cartridge::new(rom).err().unwrap()
+}
+
+#[test]
+fn ines_header() {
+    let cartridge = Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap();
+
+    assert_eq!(cartridge.mapper, 0);
+    assert_eq!(cartridge.mirroring, Mirroring::Horizontal);
+    assert_eq!(cartridge.prg_rom.len(), PRG_BANK_SIZE);
+    assert_eq!(cartridge.chr.len(), CHR_BANK_SIZE);
+    assert!(!cartridge.battery && !cartridge.chr_ram);
+    assert_eq!(cartridge.region, None);
+
+    let mut rom = nrom_with_program(&IDLE_LOOP);
+    rom[6] |= 0b00000001;
+    assert_eq!(Cartridge::new(&rom).unwrap().mirroring, Mirroring::Vertical);
+
+    rom[6] |= 0b00001000;
+    assert_eq!(Cartridge::new(&rom).unwrap().mirroring, Mirroring::FourScreen);
+
+    let cartridge = Cartridge::new(&chr_ram_rom(&IDLE_LOOP)).unwrap();
+    assert!(cartridge.chr_ram);
+    assert_eq!(cartridge.chr, [0; CHR_BANK_SIZE]);
+}
+
+#[test]
+fn bad_roms_are_rejected() {
+    let rom = nrom_with_program(&IDLE_LOOP);
+
+    let mut magic = rom.clone();
+    magic[3] = 0x1b;
+    assert_eq!(error(&magic), CartridgeError::BadMagic);
+
+    assert_eq!(error(&rom[..HEADER_SIZE - 1]), CartridgeError::Truncated);
+    // short of the chr rom, and of the prg rom
+    assert_eq!(error(&rom[..rom.len() - 1]), CartridgeError::Truncated);
+    assert_eq!(error(&rom[..HEADER_SIZE + PRG_BANK_SIZE - 1]), CartridgeError::Truncated);
+
+    let mut no_prg = rom.clone();
+    no_prg[4] = 0;
+    assert_eq!(error(&no_prg), CartridgeError::Truncated);
+}
+
+#[test]
+fn mapper_number_takes_both_nibbles() {
+    let mut rom = nrom_with_program(&IDLE_LOOP);
+
+    rom[6] |= 0x40;
+    assert_eq!(error(&rom), CartridgeError::UnsupportedMapper(4));
+
+    rom[7] |= 0x10;
+    assert_eq!(error(&rom), CartridgeError::UnsupportedMapper(0x14));
+}
+
+#[test]
+fn trainer_is_skipped() {
+    let rom = nrom_with_program(&IDLE_LOOP);
+    let mut trained = rom[..HEADER_SIZE].to_vec();
+
+    trained[6] |= 0b00000100;
+    trained.extend_from_slice(&[0xff; TRAINER_SIZE]);
+    trained.extend_from_slice(&rom[HEADER_SIZE..]);
+
+    let cartridge = Cartridge::new(&trained).unwrap();
+    assert_eq!(&cartridge.prg_rom[..3], &IDLE_LOOP);
+
+    // the trainer counts towards the size
+    assert_eq!(error(&trained[..trained.len() - 1]), CartridgeError::Truncated);
+}
+
+#[test]
+fn nes2_region() {
+    let mut rom = nrom_with_program(&IDLE_LOOP);
+
+    // ines headers don't say
+    rom[12] = 1;
+    assert_eq!(Cartridge::new(&rom).unwrap().region, None);
+
+    rom[7] |= 0b00001000;
+
+    for (value, region) in [(0, Some(Region::Ntsc)), (1, Some(Region::Pal)), (2, None), (3, Some(Region::Dendy))] {
+        rom[12] = value;
+        assert_eq!(Cartridge::new(&rom).unwrap().region, region);
+    }
+}
+
+#[test]
+fn sram_needs_a_battery() {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&SHIFT_SRAM)).unwrap();
+
+    assert!(!nes.has_battery());
+    assert!(nes.sram().is_empty());
+    assert!(!nes.load_sram(&[0; PRG_RAM_SIZE]));
+
+    // the ram still works, there's just nothing to persist
+    nes.write(0x6000, 1);
+    nes.tick_frame();
+    assert_eq!(nes.read(0x6000), 2);
+    assert!(!nes.sram_dirty());
+}
+
+#[test]
+fn sram_is_marked_dirty_until_saved() {
+    let mut nes = Nes::new();
+    nes.load_rom(&battery_rom(&SHIFT_SRAM)).unwrap();
+
+    assert!(nes.has_battery());
+    assert_eq!(nes.sram(), [0; PRG_RAM_SIZE]);
+    assert!(!nes.load_sram(&[1; PRG_RAM_SIZE - 1]));
+
+    let mut sram = vec![0; PRG_RAM_SIZE];
+    sram[0] = 1;
+    sram[PRG_RAM_SIZE - 1] = 0x42;
+
+    assert!(nes.load_sram(&sram));
+    assert!(!nes.sram_dirty());
+    assert_eq!(nes.read(0x7fff), 0x42);
+
+    nes.tick_frame();
+    assert!(nes.sram_dirty());
+    assert_eq!(nes.sram()[0], 2);
+
+    nes.mark_sram_saved();
+    assert!(!nes.sram_dirty());
+
+    // writing back what's already there doesn't count
+    nes.write(0x7fff, 0x42);
+    assert!(!nes.sram_dirty());
+
+    // and a loaded state may not match what was persisted
+    let state = nes.save_state();
+    nes.load_state(&state).unwrap();
+    assert!(nes.sram_dirty());
+}
+
+#[test]
+fn sram_file_round_trip() {
+    let dir = env::temp_dir().join(format!("sram-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = sram::path_for_rom(&dir.join("game.nes"));
+    assert_eq!(path, dir.join("game.sav"));
+
+    let mut nes = Nes::new();
+    nes.load_rom(&battery_rom(&SHIFT_SRAM)).unwrap();
+
+    // no file yet, and nothing to write while clean
+    assert!(!nes.load_sram_file(&path).unwrap());
+    nes.save_sram_file(&path).unwrap();
+    assert!(!path.exists());
+
+    nes.write(0x6000, 3);
+    nes.tick_frame();
+    nes.save_sram_file(&path).unwrap();
+    assert!(!nes.sram_dirty());
+
+    let mut again = Nes::new();
+    again.load_rom(&battery_rom(&SHIFT_SRAM)).unwrap();
+    assert!(again.load_sram_file(&path).unwrap());
+    assert_eq!(again.sram(), nes.sram());
+    assert_eq!(again.read(0x6000), 6);
+
+    // a cartridge without a battery leaves the file alone
+    let mut plain = Nes::new();
+    plain.load_rom(&nrom_with_program(&SHIFT_SRAM)).unwrap();
+    assert!(!plain.load_sram_file(&path).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
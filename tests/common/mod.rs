@@ -0,0 +1,27 @@
+#![allow(dead_code)]
+
+// a 16k nrom image with `program` at $c000 and the reset vector pointing at it
+pub fn nrom_with_program(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![b'N', b'E', b'S', 0x1a, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0; 16 * 1024];
+
+    prg[..program.len()].copy_from_slice(program);
+    prg[0x3ffc] = 0x00;
+    prg[0x3ffd] = 0xc0;
+
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 8 * 1024]);
+    rom
+}
+
+// the same with 8k of chr ram instead of rom, so tests can draw tiles
+pub fn chr_ram_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = nrom_with_program(program);
+
+    rom[5] = 0;
+    rom.truncate(16 + 16 * 1024);
+    rom
+}
+
+// CLC; BCC -2, spins forever without touching memory
+pub const IDLE_LOOP: [u8; 3] = [0x18, 0x90, 0xfd];
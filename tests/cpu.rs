@@ -0,0 +1,45 @@
+use nes::bus::Bus;
+use nes::cartridge::Cartridge;
+use nes::cpu::Cpu;
+
+mod common;
+
+use common::nrom_with_program;
+
+// ADC #$01; ADC $10; ADC $10f0,X; ADC $1000,X; CLC; BCC -3
+const PROGRAM: [u8; 13] = [0x69, 0x01, 0x65, 0x10, 0x7d, 0xf0, 0x10, 0x7d, 0x00, 0x10, 0x18, 0x90, 0xfd];
+
+// the cycle each of the first `count` instructions starts on
+fn starts(cpu: &mut Cpu, bus: &mut Bus, count: usize) -> Vec<u64> {
+    let mut starts = vec![];
+
+    while starts.len() < count {
+        // a whole instruction runs on its first cycle, the rest are skipped
+        if cpu.skip_ticks == 0 {
+            starts.push(cpu.cycles);
+        }
+
+        cpu.tick(bus);
+    }
+
+    starts
+}
+
+#[test]
+fn instructions_take_their_documented_cycles() {
+    let mut bus = Bus::new();
+    bus.insert(Cartridge::new(&nrom_with_program(&PROGRAM)).unwrap());
+
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    cpu.x = 0x20;
+
+    let starts = starts(&mut cpu, &mut bus, 9);
+    let lengths: Vec<u64> = starts.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+    // reset is 7 cycles before the first fetch
+    assert_eq!(starts[0], 7);
+
+    // the page cross on $10f0,X costs one more, taken branches one more
+    assert_eq!(lengths, [2, 3, 5, 4, 2, 3, 2, 3]);
+}
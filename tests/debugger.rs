@@ -0,0 +1,210 @@
+use nes::Nes;
+use nes::debugger::BreakReason;
+use nes::expr::{Expr, ExprError};
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+fn nes_with_program(program: &[u8]) -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(program)).unwrap();
+    nes
+}
+
+fn reason(nes: &Nes) -> Option<BreakReason> {
+    nes.debugger().reason.clone()
+}
+
+fn pc(nes: &Nes) -> u16 {
+    nes.cpu().pc
+}
+
+#[test]
+fn breakpoint_stops_before_the_instruction_and_resumes_past_it() {
+    let mut nes = nes_with_program(&IDLE_LOOP);
+
+    nes.add_breakpoint(0xc001, None).unwrap();
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Breakpoint(0xc001)));
+    assert_eq!(pc(&nes), 0xc001);
+    assert_eq!(nes.frame(), 0);
+
+    // paused frames don't run
+    let cycles = nes.cpu().cycles;
+    nes.tick_frame();
+    assert_eq!(nes.cpu().cycles, cycles);
+
+    // and the next pass around the loop hits it again
+    nes.resume();
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Breakpoint(0xc001)));
+    assert_eq!(nes.cpu().cycles, cycles + 5);
+
+    // the rest of the frame still runs once the breakpoint is gone
+    nes.remove_breakpoint(0xc001);
+    nes.resume();
+    nes.tick_frame();
+
+    assert!(!nes.paused());
+    assert_eq!(nes.frame(), 1);
+
+    // the first frame ends as vblank starts on dot 1 of line 241
+    assert_eq!(nes.cpu().cycles, (241 * 341 + 1u64).div_ceil(3));
+    assert_eq!(nes.bus().ppu.scanline, 241);
+}
+
+#[test]
+fn conditional_breakpoint() {
+    // ADC #$01; BCC $c000
+    let mut nes = nes_with_program(&[0x69, 0x01, 0x90, 0xfc]);
+
+    nes.add_breakpoint(0xc002, Some("a == 3 && !(p & $01)".to_string())).unwrap();
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Breakpoint(0xc002)));
+    assert_eq!(nes.cpu().a, 3);
+
+    assert!(nes.add_breakpoint(0xc000, Some("a ==".to_string())).is_err());
+    assert!(nes.add_breakpoint(0xc000, Some("[$10".to_string())).is_err());
+    assert!(nes.add_breakpoint(0xc000, Some("q".to_string())).is_err());
+    assert!(nes.add_breakpoint(0xc000, Some("a == é".to_string())).is_err());
+}
+
+#[test]
+fn conditions_with_non_ascii_are_rejected() {
+    assert_eq!(Expr::parse("a == é"), Err(ExprError("unexpected `é`".to_string())));
+    assert_eq!(Expr::parse("€"), Err(ExprError("unexpected `€`".to_string())));
+    assert_eq!(Expr::parse("x > 1 && 🦀"), Err(ExprError("unexpected `🦀`".to_string())));
+}
+
+#[test]
+fn write_watchpoint_with_condition() {
+    // ASL $10; CLC; BCC $c000
+    let mut nes = nes_with_program(&[0x06, 0x10, 0x18, 0x90, 0xfb]);
+
+    nes.write(0x10, 0x01);
+    nes.add_watchpoint(0x10, 0x10, false, true, Some("value == $80".to_string())).unwrap();
+    nes.tick_frame();
+
+    assert_eq!(
+        reason(&nes),
+        Some(BreakReason::Watchpoint { addr: 0x10, value: 0x80, write: true }),
+    );
+    assert_eq!(nes.read(0x10), 0x80);
+
+    nes.clear_watchpoints();
+    nes.resume();
+    nes.tick_frame();
+
+    assert!(!nes.paused());
+}
+
+#[test]
+fn read_watchpoint_ignores_outside_accesses() {
+    // AND $0300; CLC; BCC $c000
+    let mut nes = nes_with_program(&[0x2d, 0x00, 0x03, 0x18, 0x90, 0xfa]);
+
+    nes.add_watchpoint(0x0300, 0x03ff, true, false, None).unwrap();
+
+    // reads from the frontend or the disassembler don't count
+    nes.read(0x0300);
+    nes.disassemble(0x0300, 0x0310);
+    nes.add_breakpoint(0xc000, None).unwrap();
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Breakpoint(0xc000)));
+
+    nes.resume();
+    nes.tick_frame();
+
+    assert_eq!(
+        reason(&nes),
+        Some(BreakReason::Watchpoint { addr: 0x0300, value: 0, write: false }),
+    );
+    assert_eq!(pc(&nes), 0xc003);
+}
+
+#[test]
+fn stepping() {
+    let mut nes = nes_with_program(&IDLE_LOOP);
+
+    nes.pause();
+    assert_eq!(nes.break_reason(), Some("paused".to_string()));
+
+    nes.step_into();
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Step));
+    assert_eq!(pc(&nes), 0xc000);
+
+    nes.step_into();
+    nes.tick_frame();
+    assert_eq!(pc(&nes), 0xc001);
+
+    // nothing to step over, so it's a single step too
+    nes.step_over();
+    nes.tick_frame();
+    assert_eq!(pc(&nes), 0xc000);
+
+    nes.run_to_scanline(100);
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Scanline(100)));
+    assert_eq!(nes.bus().ppu.scanline, 100);
+}
+
+#[test]
+fn breaks_on_brk_and_unknown_opcodes() {
+    // CLC; BRK; <unknown>
+    let mut nes = nes_with_program(&[0x18, 0x00, 0x02]);
+
+    nes.set_break_on(true, true, false, false);
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Brk(0xc001)));
+
+    // the irq vector is zero, so point the cpu at the bad opcode instead
+    nes.machine_mut().cpu.pc = 0xc002;
+    nes.resume();
+    nes.tick_frame();
+
+    let unknown = Some(BreakReason::UnknownOpcode { pc: 0xc002, opcode: 0x02 });
+
+    assert_eq!(reason(&nes), unknown);
+
+    // running it would only crash, so it stays put
+    nes.resume();
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), unknown);
+    assert_eq!(nes.break_reason(), Some("unknown opcode $02 at $C002".to_string()));
+}
+
+#[test]
+fn breaks_on_interrupts() {
+    let mut nes = nes_with_program(&IDLE_LOOP);
+
+    nes.set_break_on(false, false, true, true);
+    nes.tick_frame();
+
+    assert!(!nes.paused());
+
+    let sp = nes.cpu().sp;
+    nes.machine_mut().cpu.nmi = true;
+    nes.tick_frame();
+
+    assert_eq!(reason(&nes), Some(BreakReason::Nmi));
+
+    nes.step_into();
+    nes.tick_frame();
+
+    // the nmi vector in the test rom is zero
+    let cpu = nes.cpu();
+
+    assert_eq!(cpu.pc, 0x0000);
+    assert_eq!(cpu.sp, sp.wrapping_sub(3));
+    assert!(!cpu.nmi);
+}
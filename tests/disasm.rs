@@ -0,0 +1,35 @@
+use nes::disasm;
+
+#[test]
+fn listing_labels_branch_targets() {
+    let program = [
+        0x18,             // CLC
+        0x69, 0x05,       // ADC #$05
+        0x90, 0xfb,       // BCC $C000
+        0x3d, 0x00, 0x02, // AND $0200,X
+        0x71, 0x10,       // ADC ($10),Y
+        0x02,             // not an opcode we know
+    ];
+
+    let lines = disasm::disassemble(&program, 0xc000);
+
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[2].target(), Some(0xc000));
+
+    assert_eq!(disasm::to_text(&lines), concat!(
+        "L_C000:\n",
+        "C000  18        CLC\n",
+        "C001  69 05     ADC #$05\n",
+        "C003  90 FB     BCC L_C000\n",
+        "C005  3D 00 02  AND $0200,X\n",
+        "C008  71 10     ADC ($10),Y\n",
+        "C00A  02        .db $02\n",
+    ));
+}
+
+#[test]
+fn targets_outside_the_listing_stay_numeric() {
+    let lines = disasm::disassemble(&[0xd0, 0x10], 0x8000);
+
+    assert_eq!(disasm::to_text(&lines), "8000  D0 10     BNE $8012\n");
+}
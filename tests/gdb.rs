@@ -0,0 +1,207 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use nes::Nes;
+use nes::gdb::GdbStub;
+
+mod common;
+
+use common::nrom_with_program;
+
+// ASL $10; CLC; BCC $c000
+const PROGRAM: [u8; 5] = [0x06, 0x10, 0x18, 0x90, 0xfb];
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn send_raw(&mut self, packet: &str) {
+        self.stream.write_all(packet.as_bytes()).unwrap();
+    }
+
+    fn send(&mut self, data: &str) -> String {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+
+        self.send_raw(&format!("${}#{:02x}", data, sum));
+        assert_eq!(self.read_byte(), b'+', "packet `{}` wasn't acked", data);
+
+        assert_eq!(self.read_byte(), b'$');
+
+        let mut reply = vec![];
+
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.send_raw("+");
+
+        let sum = reply.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(std::str::from_utf8(&checksum).unwrap(), format!("{:02x}", sum));
+
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+#[test]
+fn loopback_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // the emulator isn't Send, so it stays here and the client gets the thread
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let mut client = Client { stream };
+
+        // a corrupt packet is nacked
+        client.send_raw("$g#00");
+        assert_eq!(client.read_byte(), b'-');
+
+        assert!(client.send("qSupported:multiprocess+").contains("qXfer:features:read+"));
+        assert!(client.send("qXfer:features:read:target.xml:0,fff").starts_with("l<?xml"));
+        assert_eq!(client.send("?"), "S05");
+
+        // a x y p sp, then pc little endian. reset leaves interrupts off
+        assert_eq!(client.send("g"), "000000040000c0");
+        assert_eq!(client.send("mc000,5"), "06101890fb");
+
+        assert_eq!(client.send("M10,1:01"), "OK");
+        assert_eq!(client.send("m10,1"), "01");
+        assert_eq!(client.send("P0=42"), "OK");
+        assert_eq!(client.send("p0"), "42");
+
+        assert_eq!(client.send("Z0,c002,1"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p5"), "02c0");
+
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send("p5"), "03c0");
+        assert_eq!(client.send("z0,c002,1"), "OK");
+
+        // ASL $10 already ran once on the way to the breakpoint
+        assert_eq!(client.send("Z2,10,1"), "OK");
+        assert_eq!(client.send("c"), "T05watch:0010;");
+        assert_eq!(client.send("m10,1"), "04");
+        assert_eq!(client.send("z2,10,1"), "OK");
+
+        assert_eq!(client.send("vMustReplyEmpty"), "");
+        assert_eq!(client.send("D"), "OK");
+    });
+
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&PROGRAM)).unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_nodelay(true).unwrap();
+
+    GdbStub::new(stream).run(&mut nes).unwrap();
+
+    client.join().unwrap();
+
+    assert!(!nes.paused());
+    assert_eq!(nes.cpu().a, 0x42);
+}
+
+#[test]
+fn detaching_takes_out_what_the_client_put_in() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let mut client = Client { stream };
+
+        // junk that isn't a command, and more memory than fits in a packet
+        assert_eq!(client.send("\u{e9}"), "");
+        assert_eq!(client.send("m0,ffffffff"), "E01");
+        assert_eq!(client.send("m0,801"), "E01");
+        assert_eq!(client.send("m0,800").len(), 0x1000);
+
+        // ASL $10 reads before it writes, either stops an access watchpoint
+        assert_eq!(client.send("Z4,10,1"), "OK");
+        assert_eq!(client.send("c"), "T05awatch:0010;");
+        assert_eq!(client.send("Z0,c002,1"), "OK");
+        assert_eq!(client.send("D"), "OK");
+    });
+
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&PROGRAM)).unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_nodelay(true).unwrap();
+
+    GdbStub::new(stream).run(&mut nes).unwrap();
+
+    client.join().unwrap();
+
+    // nobody is attached to stop for anymore
+    for _ in 0..3 {
+        nes.tick_frame();
+    }
+
+    assert!(!nes.paused());
+    assert!(nes.debugger().breakpoints.is_empty());
+    assert!(nes.debugger().watchpoints.is_empty());
+}
+
+#[test]
+fn killing_takes_out_what_the_client_put_in() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        let mut client = Client { stream };
+
+        // ppu registers aren't memory, a write there doesn't take
+        assert_eq!(client.send("M2000,1:80"), "E01");
+        assert_eq!(client.send("M1fff,2:1122"), "E01");
+        // the byte before them is ram, and keeps its half of the write
+        assert_eq!(client.send("m7ff,1"), "11");
+
+        assert_eq!(client.send("Z0,c002,1"), "OK");
+        assert_eq!(client.send("Z2,10,1"), "OK");
+
+        // kill is acked but never answered
+        client.send_raw(&format!("$k#{:02x}", b'k'));
+        assert_eq!(client.read_byte(), b'+');
+    });
+
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&PROGRAM)).unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_nodelay(true).unwrap();
+
+    GdbStub::new(stream).run(&mut nes).unwrap();
+
+    client.join().unwrap();
+
+    // the write to $2000 didn't go through the bus
+    assert_eq!(nes.machine().bus.ppu.ctrl, 0);
+
+    for _ in 0..3 {
+        nes.tick_frame();
+    }
+
+    assert!(!nes.paused());
+    assert!(nes.debugger().breakpoints.is_empty());
+    assert!(nes.debugger().watchpoints.is_empty());
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::Path;
+use nes::Nes;
+
+mod common;
+
+use common::{chr_ram_rom, IDLE_LOOP};
+
+// compares the frame rebuilt from nametable, palette and oam state, see
+// `viewer::screen`, against a known good one. nothing here reads a
+// rendered framebuffer, so these catch changes to that state and to the
+// rebuild but not rendering regressions in the ppu. when a change moves a
+// hash on purpose the new frame is left in target/golden/ to look over
+// before the hash here is updated
+fn check(nes: &Nes, name: &str, expected: u64) {
+    let hash = nes.reconstructed_frame_hash();
+
+    if hash != expected {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+        let path = dir.join(format!("{}.png", name));
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, nes.screenshot()).unwrap();
+
+        panic!("{} hashed to {:016x}, expected {:016x}, see {}", name, hash, expected, path.display());
+    }
+}
+
+fn write_vram(nes: &mut Nes, addr: u16, bytes: &[u8]) {
+    nes.read(0x2002);
+    nes.write(0x2006, (addr >> 8) as u8);
+    nes.write(0x2006, addr as u8);
+
+    for byte in bytes {
+        nes.write(0x2007, *byte);
+    }
+}
+
+fn scroll(nes: &mut Nes, ctrl: u8, x: u8, y: u8) {
+    nes.write(0x2000, ctrl);
+    nes.read(0x2002);
+    nes.write(0x2005, x);
+    nes.write(0x2005, y);
+}
+
+// a checkerboard of two tiles over all 4 nametables with a few sprites on
+// top, nothing drawn until rendering is turned on
+fn scene() -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&chr_ram_rom(&IDLE_LOOP)).unwrap();
+
+    // tile 1 uses all 4 colours in stripes, tile 2 is a solid colour 1
+    // block with a colour 0 hole in the middle
+    write_vram(&mut nes, 0x0010, &[0xf0; 8]);
+    write_vram(&mut nes, 0x0018, &[0xcc; 8]);
+    write_vram(&mut nes, 0x0020, &[0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff]);
+
+    write_vram(&mut nes, 0x3f00, &[
+        0x0f, 0x16, 0x2a, 0x12, 0x0f, 0x27, 0x19, 0x30, 0x0f, 0x01, 0x11, 0x21, 0x0f, 0x06, 0x17, 0x28,
+        0x0f, 0x30, 0x27, 0x11, 0x0f, 0x14, 0x24, 0x34, 0x0f, 0x0a, 0x1a, 0x2a, 0x0f, 0x00, 0x10, 0x20,
+    ]);
+
+    for nametable in 0..4u16 {
+        let tiles: Vec<u8> = (0..960).map(|i| ((i / 32 + i % 32 + nametable as usize) & 1) as u8).collect();
+        let attributes: Vec<u8> = (0..64).map(|i| (i as u8).wrapping_mul(0x1b).wrapping_add(nametable as u8)).collect();
+
+        write_vram(&mut nes, 0x2000 + nametable * 0x400, &tiles);
+        write_vram(&mut nes, 0x23c0 + nametable * 0x400, &attributes);
+    }
+
+    // y, tile, attributes, x. the third is flipped and behind the
+    // background, the last two overlap with the lower index on top
+    let sprites = [
+        [0x10, 2, 0b00000000, 0x10],
+        [0x30, 2, 0b00000001, 0x80],
+        [0x50, 1, 0b11100010, 0x40],
+        [0x80, 2, 0b00000011, 0xc0],
+        [0x84, 2, 0b00000000, 0xc4],
+    ];
+
+    nes.write(0x2003, 0);
+
+    for value in sprites.iter().flatten() {
+        nes.write(0x2004, *value);
+    }
+
+    scroll(&mut nes, 0, 0, 0);
+    nes
+}
+
+#[test]
+fn reconstructed_backdrop() {
+    let mut nes = scene();
+
+    nes.tick_frame();
+    check(&nes, "backdrop", 0x1719dca5cef7a325);
+}
+
+#[test]
+fn reconstructed_background() {
+    let mut nes = scene();
+
+    nes.write(0x2001, 0x08);
+    nes.tick_frame();
+    check(&nes, "background", 0x7e69b5cef1077a65);
+}
+
+#[test]
+fn reconstructed_sprites() {
+    let mut nes = scene();
+
+    nes.write(0x2001, 0x18);
+    nes.tick_frame();
+    check(&nes, "sprites", 0xb37d8cde81cc2a01);
+}
+
+#[test]
+fn reconstructed_scrolled() {
+    let mut nes = scene();
+
+    // starting in the bottom right nametable, wrapping into the others
+    scroll(&mut nes, 0b00000011, 100, 37);
+    nes.write(0x2001, 0x18);
+    nes.tick_frame();
+    check(&nes, "scrolled", 0x9b6f13e2ba0e6f49);
+}
@@ -0,0 +1,191 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use nes::Nes;
+use nes::audio::CHANNELS;
+use nes::headless::InputScript;
+use nes::input::{BUTTON_A, BUTTON_START};
+use nes::trace::WriteSink;
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+#[test]
+fn input_script_holds_buttons_until_the_next_press() {
+    let script = InputScript::parse("
+        # start on the title screen, then a on player 2
+        10 1 ....T...
+        12 2 .......A
+        20 1 ........
+    ").unwrap();
+
+    let mut nes = Nes::new();
+    let mut held = vec![];
+
+    for frame in 0..25 {
+        script.apply(&mut nes, frame);
+        held.push(nes.bus().input.buttons);
+    }
+
+    assert_eq!(held[9], [0; 4]);
+    assert_eq!(held[10], [BUTTON_START, 0, 0, 0]);
+    assert_eq!(held[15], [BUTTON_START, BUTTON_A, 0, 0]);
+    assert_eq!(held[24], [0, BUTTON_A, 0, 0]);
+}
+
+#[test]
+fn input_script_reports_bad_lines() {
+    let error = InputScript::parse("1 1 ....T...\n2 5 ....T...").unwrap_err();
+    assert_eq!(error.line, 2);
+
+    assert!(InputScript::parse("1 1 T").is_err());
+    assert!(InputScript::parse("x 1 ....T...").is_err());
+}
+
+#[test]
+fn runner_dumps_what_it_was_asked_for() {
+    let dir = env::temp_dir().join(format!("headless-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let rom = dir.join("idle.nes");
+    fs::write(&rom, nrom_with_program(&IDLE_LOOP)).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_headless"))
+        .arg(&rom)
+        .args(["--frames", "4", "--every", "2"])
+        .arg("--screenshot").arg(dir.join("last.png"))
+        .arg("--screenshots").arg(dir.join("shots"))
+        .arg("--trace").arg(dir.join("trace.log"))
+        .arg("--ram").arg(dir.join("ram.bin"))
+        .arg("--wav").arg(dir.join("mix.wav"))
+        .arg("--stems").arg(dir.join("stems"))
+        .args(["--sample-rate", "8000", "--float"])
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    assert_eq!(&fs::read(dir.join("last.png")).unwrap()[..8], b"\x89PNG\r\n\x1a\n");
+    assert!(dir.join("shots/frame000002.png").exists());
+    assert!(dir.join("shots/frame000004.png").exists());
+    assert!(!dir.join("shots/frame000003.png").exists());
+    assert_eq!(fs::read(dir.join("ram.bin")).unwrap().len(), 2048);
+
+    let mix = fs::read(dir.join("mix.wav")).unwrap();
+    assert_eq!(&mix[..4], b"RIFF");
+    assert_eq!(u16::from_le_bytes([mix[34], mix[35]]), 32);
+
+    for name in CHANNELS.iter() {
+        assert_eq!(fs::read(dir.join(format!("stems/{}.wav", name))).unwrap().len(), mix.len());
+    }
+
+    let trace = fs::read_to_string(dir.join("trace.log")).unwrap();
+    assert!(trace.starts_with("C000  18        CLC"));
+
+    // the hash printed is the one the library gives for the same frames
+    let output = Command::new(env!("CARGO_BIN_EXE_headless"))
+        .arg(&rom)
+        .args(["--frames", "4", "--hash"])
+        .arg("--hashes").arg(dir.join("hashes.txt"))
+        .output()
+        .unwrap();
+
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+
+    for _ in 0..4 {
+        nes.tick_frame();
+    }
+
+    let hash = format!("{:016x}", nes.reconstructed_frame_hash());
+    let hashes = fs::read_to_string(dir.join("hashes.txt")).unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), hash);
+    assert_eq!(hashes.lines().count(), 4);
+    assert_eq!(hashes.lines().last().unwrap(), format!("4 {}", hash));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_headless")).arg("--frames").stderr(Stdio::null()).status().unwrap();
+    assert_eq!(status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runner_keeps_battery_saves() {
+    let dir = env::temp_dir().join(format!("headless-sav-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    // ASL $6000; CLC; BCC -2 on a battery backed board
+    let mut rom = nrom_with_program(&[0x0e, 0x00, 0x60, 0x18, 0x90, 0xfd]);
+    rom[6] |= 0b00000010;
+
+    let path = dir.join("game.nes");
+    fs::write(&path, rom).unwrap();
+
+    let run = |sav: Option<&str>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_headless"));
+        command.arg(&path).args(["--frames", "1"]);
+
+        if let Some(sav) = sav {
+            command.arg("--sav").arg(dir.join(sav));
+        }
+
+        assert!(command.status().unwrap().success());
+    };
+
+    // shifting the zeroed ram changes nothing, so there's nothing to save
+    run(None);
+    assert!(!dir.join("game.sav").exists());
+
+    let mut sav = vec![0; 8 * 1024];
+    sav[0] = 1;
+    fs::write(dir.join("game.sav"), &sav).unwrap();
+
+    // each run picks up where the last left off
+    run(None);
+    run(None);
+    assert_eq!(fs::read(dir.join("game.sav")).unwrap()[0], 4);
+
+    fs::write(dir.join("other.sav"), &sav).unwrap();
+    run(Some("other.sav"));
+    assert_eq!(fs::read(dir.join("other.sav")).unwrap()[0], 2);
+    assert_eq!(fs::read(dir.join("game.sav")).unwrap()[0], 4);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// takes `room` bytes, then the disk is full
+struct Full {
+    room: usize,
+}
+
+impl Write for Full {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.room {
+            return Err(io::Error::other("disk full"));
+        }
+
+        self.room -= buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_write_errors_are_reported() {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+    assert!(nes.flush_trace().is_ok());
+
+    nes.set_trace_sink(Box::new(WriteSink::new(Full { room: 1000 })));
+    nes.tick_frame();
+
+    // the emulator carries on, the error comes out of the flush once
+    assert_eq!(nes.flush_trace().unwrap_err().to_string(), "disk full");
+    assert!(nes.flush_trace().is_ok());
+}
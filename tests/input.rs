@@ -0,0 +1,61 @@
+use nes::bus::{BusRead, BusWrite};
+use nes::input::{Input, InputMode, BUTTON_A, BUTTON_B, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START, BUTTON_UP};
+
+fn strobe(input: &mut Input) {
+    input.write(0x4016, 1);
+    input.write(0x4016, 0);
+}
+
+// the next `count` reads of `addr`
+fn reads(input: &mut Input, addr: usize, count: usize) -> Vec<u8> {
+    (0..count).map(|_| input.read(addr).unwrap()).collect()
+}
+
+// `buttons` as its 8 reads, a first
+fn bits(buttons: u8) -> Vec<u8> {
+    (0..8).map(|bit| (buttons >> bit) & 1).collect()
+}
+
+#[test]
+fn four_score_signature() {
+    let mut input = Input::new();
+    input.set_mode(InputMode::FourScore);
+    input.buttons = [BUTTON_A, BUTTON_B, BUTTON_START, BUTTON_RIGHT];
+    strobe(&mut input);
+
+    // players 1 and 3 on $4016, 2 and 4 on $4017, then each port's id
+    for (addr, first, second, signature) in [
+        (0x4016, BUTTON_A, BUTTON_START, [0, 0, 0, 1, 0, 0, 0, 0]),
+        (0x4017, BUTTON_B, BUTTON_RIGHT, [0, 0, 1, 0, 0, 0, 0, 0]),
+    ] {
+        assert_eq!(reads(&mut input, addr, 8), bits(first));
+        assert_eq!(reads(&mut input, addr, 8), bits(second));
+        assert_eq!(reads(&mut input, addr, 8), signature);
+
+        // all of it read, d0 stays high
+        assert_eq!(reads(&mut input, addr, 4), [1; 4]);
+    }
+}
+
+#[test]
+fn famicom_players_3_and_4_on_d1() {
+    let mut input = Input::new();
+    input.set_mode(InputMode::Famicom);
+    input.buttons = [BUTTON_A, BUTTON_B, BUTTON_SELECT, BUTTON_UP];
+    strobe(&mut input);
+
+    for (addr, first, second) in [(0x4016, BUTTON_A, BUTTON_SELECT), (0x4017, BUTTON_B, BUTTON_UP)] {
+        let expected: Vec<u8> = bits(first).iter().zip(bits(second)).map(|(d0, d1)| d0 | (d1 << 1)).collect();
+
+        assert_eq!(input.peek(addr), Some(expected[0]));
+        assert_eq!(reads(&mut input, addr, 8), expected);
+        assert_eq!(reads(&mut input, addr, 2), [0b11; 2]);
+    }
+
+    // the same buttons on a standard port leave d1 alone
+    input.set_mode(InputMode::Standard);
+    strobe(&mut input);
+
+    assert_eq!(reads(&mut input, 0x4016, 8), bits(BUTTON_A));
+    assert_eq!(reads(&mut input, 0x4016, 2), [1; 2]);
+}
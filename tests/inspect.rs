@@ -0,0 +1,92 @@
+use nes::Nes;
+use nes::input::{BUTTON_A, BUTTON_B};
+use nes::inspect::MemorySpace;
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+fn idle_nes() -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+    nes
+}
+
+#[test]
+fn peek_has_no_side_effects() {
+    let mut nes = idle_nes();
+
+    nes.machine_mut().bus.ppu.status = 0x80;
+
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0x2002), Some(0x80));
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0x3ffa), Some(0x80));
+    assert_eq!(nes.read(0x2002), 0x80);
+    assert_eq!(nes.read(0x2002), 0x00);
+
+    nes.set_buttons(0, BUTTON_B);
+    nes.write(0x4016, 1);
+    nes.write(0x4016, 0);
+
+    // a is up first, and peeking doesn't move on to b
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0x4016), Some(0));
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0x4016), Some(0));
+    assert_eq!(nes.read(0x4016), 0);
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0x4016), Some(1));
+    assert_eq!(nes.read(0x4016), 1);
+
+    // while strobe is high it's always a
+    nes.set_buttons(0, BUTTON_A);
+    nes.write(0x4016, 1);
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0x4016), Some(1));
+}
+
+#[test]
+fn every_space_is_addressable() {
+    let mut nes = idle_nes();
+
+    let sizes = [
+        (MemorySpace::CpuBus, 0x10000),
+        (MemorySpace::Ram, 0x800),
+        (MemorySpace::PrgRom, 0x4000),
+        (MemorySpace::PrgRam, 0x2000),
+        (MemorySpace::Chr, 0x2000),
+        (MemorySpace::Ciram, 0x800),
+        (MemorySpace::Palette, 0x20),
+        (MemorySpace::Oam, 0x100),
+    ];
+
+    for (space, size) in sizes.iter() {
+        assert_eq!(nes.memory_size(*space), *size, "{:?}", space);
+        assert!(nes.poke(*space, size - 1, 0x5a), "{:?}", space);
+        assert_eq!(nes.peek(*space, size - 1), Some(0x5a), "{:?}", space);
+        assert_eq!(nes.peek(*space, *size), None, "{:?}", space);
+        assert!(!nes.poke(*space, *size, 0), "{:?}", space);
+    }
+
+    // the cpu bus pokes whatever memory is behind an address
+    assert!(nes.poke(MemorySpace::CpuBus, 0x0801, 0x11));
+    assert_eq!(nes.peek(MemorySpace::Ram, 1), Some(0x11));
+
+    assert!(nes.poke(MemorySpace::CpuBus, 0x8002, 0x22));
+    assert_eq!(nes.peek(MemorySpace::CpuBus, 0xc002), Some(0x22));
+
+    // but leaves registers alone
+    assert!(!nes.poke(MemorySpace::CpuBus, 0x2000, 0x80));
+    assert_eq!(nes.bus().ppu.ctrl, 0);
+
+    assert_eq!(nes.peek_range(MemorySpace::Palette, 0x1e, 4), vec![0, 0x5a]);
+}
+
+#[test]
+fn hex_dump() {
+    let mut nes = idle_nes();
+
+    for (i, byte) in b"Hello, NES!\0\x7f".iter().enumerate() {
+        nes.poke(MemorySpace::Ram, 0x10 + i, *byte);
+    }
+
+    assert_eq!(nes.hex_dump(MemorySpace::Ram, 0x10, 20), concat!(
+        "0010: 48 65 6C 6C 6F 2C 20 4E 45 53 21 00 7F 00 00 00  |Hello, NES!.....|\n",
+        "0020: 00 00 00 00                                      |....|\n",
+    ));
+}
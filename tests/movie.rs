@@ -0,0 +1,65 @@
+use nes::movie::{Frame, Movie, MovieError, COMMAND_SOFT_RESET};
+use nes::region::Region;
+
+const FM2: &str = "version 3
+emuVersion 22020
+rerecordCount 12
+palFlag 0
+romFilename some game
+fourscore 0
+port0 1
+port1 0
+port2 0
+comment author someone
+|0|........|........||
+|1|R......A|........||
+|0|...UT...|RLDUTSBA||
+";
+
+#[test]
+fn fm2_header_and_input_lines() {
+    let movie = Movie::from_fm2(FM2).unwrap();
+
+    assert_eq!(movie.region, Region::Ntsc);
+    assert_eq!(movie.rerecords, 12);
+    assert_eq!(movie.rom_filename, "some game");
+    assert!(!movie.four_score && movie.start.is_none());
+
+    // port1 has nothing plugged in, so its column is ignored
+    assert_eq!(movie.frames, [
+        Frame { commands: 0, buttons: [0, 0, 0, 0] },
+        Frame { commands: COMMAND_SOFT_RESET, buttons: [0b10000001, 0, 0, 0] },
+        Frame { commands: 0, buttons: [0b00011000, 0, 0, 0] },
+    ]);
+}
+
+#[test]
+fn fm2_round_trip() {
+    let mut movie = Movie::from_fm2(FM2).unwrap();
+    movie.four_score = true;
+    movie.frames[2].buttons = [0b00011000, 0b11111111, 0b00000100, 0b01000000];
+
+    let fm2 = movie.to_fm2().unwrap();
+    assert!(fm2.contains("rerecordCount 12\npalFlag 0\nromFilename some game\nfourscore 1\n"));
+    assert!(fm2.ends_with("|0|...UT...|RLDUTSBA|.....S..|.L......||\n"));
+
+    let again = Movie::from_fm2(&fm2).unwrap();
+    assert_eq!(again.frames, movie.frames);
+    assert_eq!((again.four_score, again.region, again.rerecords), (true, Region::Ntsc, 12));
+    assert_eq!(again.rom_filename, movie.rom_filename);
+    assert_eq!(again.to_fm2().unwrap(), fm2);
+}
+
+#[test]
+fn fm2_errors() {
+    assert_eq!(
+        Movie::from_fm2("version 3\n|0|....|........||\n").unwrap_err(),
+        MovieError::Parse(2, "gamepad field `....` should be 8 wide".to_string()),
+    );
+    assert_eq!(Movie::from_fm2("version 3\n|x|\n").unwrap_err(), MovieError::Parse(2, "bad command field".to_string()));
+    assert_eq!(Movie::from_fm2("version 2\n").unwrap_err(), MovieError::Unsupported("fm2 version"));
+    assert_eq!(Movie::from_fm2("port0 2\n").unwrap_err(), MovieError::Unsupported("zapper"));
+
+    let from_state = Movie { start: Some(vec![]), ..Movie::new() };
+    assert!(from_state.to_fm2().is_err());
+}
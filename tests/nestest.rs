@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::Path;
+use nes::Nes;
+use nes::trace::TraceBuffer;
+
+mod common;
+
+use common::nrom_with_program;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/nestest");
+
+// lines of context shown before the first divergence
+const CONTEXT: usize = 5;
+
+// boots `rom` in nestest's automation mode and traces up to `limit`
+// instructions, stopping early if the cpu halts
+fn trace(rom: &[u8], x: u8, y: u8, limit: usize) -> Vec<String> {
+    let mut nes = Nes::new();
+    let buffer = TraceBuffer::new(usize::MAX);
+
+    nes.load_rom(rom).expect("rom should load");
+    nes.set_trace_sink(Box::new(buffer.clone()));
+
+    {
+        let machine = nes.machine_mut();
+        let cpu = &mut machine.cpu;
+
+        // the state nestest.log expects once the reset sequence is done
+        cpu.pc = 0xc000;
+        cpu.sp = 0xfd;
+        cpu.p = 0x24;
+        cpu.x = x;
+        cpu.y = y;
+        cpu.cycles = 7;
+        cpu.skip_ticks = 0;
+
+        machine.bus.ppu.dot = 21;
+    }
+
+    let mut lines = vec![];
+
+    while lines.len() < limit {
+        if let Some(opcode) = nes.cpu().halted {
+            eprintln!("cpu halted on ${:02X} after {} lines", opcode, lines.len());
+            break;
+        }
+
+        nes.tick_cpu();
+        lines.extend(buffer.take());
+    }
+
+    lines.truncate(limit);
+    lines
+}
+
+fn assert_matches_log(expected: &str, actual: &[String]) {
+    let expected: Vec<&str> = expected.lines().map(|line| line.trim_end()).collect();
+
+    for (i, line) in expected.iter().enumerate() {
+        let actual_line = actual.get(i).map(|line| line.as_str());
+
+        if actual_line == Some(*line) {
+            continue;
+        }
+
+        let context: Vec<&str> = expected[i.saturating_sub(CONTEXT)..i].to_vec();
+
+        panic!(
+            "trace diverges at line {}\n\ncontext:\n{}\n\nexpected:\n{}\nactual:\n{}\n",
+            i + 1,
+            context.join("\n"),
+            line,
+            actual_line.unwrap_or("<cpu stopped>"),
+        );
+    }
+}
+
+#[test]
+fn smoke_program_matches_reference_log() {
+    let program = [
+        0x18,             // CLC
+        0x69, 0x05,       // ADC #$05
+        0xc0, 0x20,       // CPY #$20
+        0xe0, 0x10,       // CPX #$10
+        0xc9, 0x06,       // CMP #$06
+        0x30, 0x02,       // BMI $C00D
+        0x00, 0x00,       // (skipped)
+        0x24, 0x00,       // BIT $00
+        0x0a,             // ASL A
+        0x2d, 0x00, 0x02, // AND $0200
+        0xd0, 0x03,       // BNE $C018
+        0xb8,             // CLV
+        0x90, 0xe8,       // BCC $C000
+    ];
+
+    let expected = fs::read_to_string(Path::new(FIXTURES).join("smoke.log")).unwrap();
+    let actual = trace(&nrom_with_program(&program), 0x10, 0x20, expected.lines().count());
+
+    assert_matches_log(&expected, &actual);
+}
+
+// nestest.nes and nestest.log aren't checked in yet, fetch them from
+// https://github.com/christopherpow/nes-test-roms/tree/master/other into
+// tests/nestest/ and run `cargo test -- --ignored`. the cpu only runs
+// ADC..CPY so far, so this fails once nestest reaches the next opcode
+#[test]
+#[ignore = "needs tests/nestest/nestest.nes and nestest.log, and fails past ADC..CPY"]
+fn nestest_matches_reference_log() {
+    let dir = Path::new(FIXTURES);
+    let rom = fs::read(dir.join("nestest.nes")).expect("tests/nestest/nestest.nes");
+    let log = fs::read_to_string(dir.join("nestest.log")).expect("tests/nestest/nestest.log");
+
+    let actual = trace(&rom, 0, 0, log.lines().count());
+
+    assert_matches_log(&log, &actual);
+}
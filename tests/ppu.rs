@@ -0,0 +1,246 @@
+use nes::{Nes, Tick, png, viewer};
+use nes::ppu::{self, Ppu};
+use nes::region::Region;
+
+mod common;
+
+use common::{chr_ram_rom, IDLE_LOOP};
+
+fn chr_ram_nes(program: &[u8]) -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&chr_ram_rom(program)).unwrap();
+    nes
+}
+
+fn set_vram_addr(nes: &mut Nes, addr: u16) {
+    nes.read(0x2002);
+    nes.write(0x2006, (addr >> 8) as u8);
+    nes.write(0x2006, addr as u8);
+}
+
+fn pixel(rgba: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+    let i = (y * width + x) * 4;
+    [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+}
+
+#[test]
+fn vram_access_through_registers() {
+    let mut nes = chr_ram_nes(&IDLE_LOOP);
+
+    // the backdrop mirrors into the sprite palettes
+    set_vram_addr(&mut nes, 0x3f10);
+    nes.write(0x2007, 0x21);
+
+    set_vram_addr(&mut nes, 0x3f00);
+    assert_eq!(nes.read(0x2007), 0x21);
+
+    // nametable reads come a byte late
+    set_vram_addr(&mut nes, 0x2400);
+    nes.write(0x2007, 0xaa);
+    nes.write(0x2007, 0xbb);
+
+    set_vram_addr(&mut nes, 0x2400);
+    nes.read(0x2007);
+    assert_eq!(nes.read(0x2007), 0xaa);
+    assert_eq!(nes.read(0x2007), 0xbb);
+
+    // horizontal mirroring, $2400 is $2000
+    assert_eq!(nes.bus().ppu.ciram[0], 0xaa);
+
+    // increment by 32 goes down a row
+    nes.write(0x2000, 0b00000100);
+    set_vram_addr(&mut nes, 0x2000);
+    nes.write(0x2007, 1);
+    nes.write(0x2007, 2);
+    assert_eq!(nes.bus().ppu.ciram[32], 2);
+}
+
+#[test]
+fn oam_dma_copies_a_page_and_stalls() {
+    // ASL $4014; CLC; BCC $c003
+    let mut nes = chr_ram_nes(&[0x0e, 0x14, 0x40, 0x18, 0x90, 0xfd]);
+
+    // reset, then the 6 cycles of ASL abs
+    for _ in 0..8 {
+        nes.tick_cpu();
+    }
+
+    // $4014 reads back open bus, the $40 high byte of the operand, which
+    // shifts to $80 and copies from prg rom
+    let page: Vec<u8> = (0..256).map(|i| nes.read(0x8000 + i)).collect();
+
+    assert_eq!(nes.bus().ppu.oam.to_vec(), page);
+    assert_eq!(&page[..3], &[0x0e, 0x14, 0x40]);
+    assert!(nes.cpu().skip_ticks >= 513);
+}
+
+// dots from one vblank to the next, for the `frames` after the first
+fn frame_lengths(ppu: &mut Ppu, frames: usize) -> Vec<u64> {
+    let mut lengths = vec![];
+    let mut frame = ppu.frame;
+
+    while ppu.frame == frame {
+        ppu.tick();
+    }
+
+    for _ in 0..frames {
+        let mut dots = 0;
+        frame = ppu.frame;
+
+        while ppu.frame == frame {
+            ppu.tick();
+            dots += 1;
+        }
+
+        lengths.push(dots);
+    }
+
+    lengths
+}
+
+#[test]
+fn odd_frames_skip_a_dot_while_rendering() {
+    let mut ppu = Ppu::new();
+    assert_eq!(frame_lengths(&mut ppu, 2), vec![341 * 262, 341 * 262]);
+
+    // frame 1 is odd
+    let mut ppu = Ppu::new();
+    ppu.mask = ppu::MASK_BACKGROUND;
+    assert_eq!(frame_lengths(&mut ppu, 4), vec![341 * 262 - 1, 341 * 262, 341 * 262 - 1, 341 * 262]);
+
+    // pal never does
+    let mut ppu = Ppu::new();
+    ppu.region = Region::Pal;
+    ppu.mask = ppu::MASK_BACKGROUND;
+    assert_eq!(frame_lengths(&mut ppu, 2), vec![341 * 312, 341 * 312]);
+}
+
+#[test]
+fn vblank_raises_nmi() {
+    let mut nes = chr_ram_nes(&IDLE_LOOP);
+
+    nes.tick_frame();
+    assert_ne!(nes.bus().ppu.status & ppu::STATUS_VBLANK, 0);
+    assert!(!nes.cpu().nmi);
+
+    // enabling nmis inside vblank fires one at once
+    nes.write(0x2000, ppu::CTRL_NMI);
+    assert!(nes.bus().ppu.nmi);
+
+    nes.tick_cpu();
+    assert!(nes.cpu().nmi || nes.cpu().pc == 0);
+
+    // the pre-render line clears the flag
+    let mut nes = chr_ram_nes(&IDLE_LOOP);
+
+    nes.tick_frame();
+    nes.write(0x2000, ppu::CTRL_NMI);
+
+    while nes.bus().ppu.scanline != 261 || nes.bus().ppu.dot < 4 {
+        nes.tick_cpu();
+    }
+
+    assert_eq!(nes.bus().ppu.status & ppu::STATUS_VBLANK, 0);
+}
+
+#[test]
+fn views() {
+    let mut nes = chr_ram_nes(&IDLE_LOOP);
+
+    // tile 1: top row solid colour 3, everything else colour 0
+    set_vram_addr(&mut nes, 0x0010);
+    nes.write(0x2007, 0xff);
+    set_vram_addr(&mut nes, 0x0018);
+    nes.write(0x2007, 0xff);
+
+    set_vram_addr(&mut nes, 0x3f00);
+    for color in [0x0f, 0x01, 0x02, 0x30] {
+        nes.write(0x2007, color);
+    }
+
+    set_vram_addr(&mut nes, 0x3f15);
+    for color in [0x11, 0x12, 0x16] {
+        nes.write(0x2007, color);
+    }
+
+    // sprite 1 uses tile 1, sprite palette 1, flipped vertically
+    nes.write(0x2003, 4);
+    for value in [0x20, 0x01, 0b10000001, 0x40] {
+        nes.write(0x2004, value);
+    }
+
+    // put tile 1 in the top left of the first nametable, scroll to 8,16
+    set_vram_addr(&mut nes, 0x2000);
+    nes.write(0x2007, 1);
+    nes.read(0x2002);
+    nes.write(0x2005, 8);
+    nes.write(0x2005, 16);
+
+    let white = [0xff, 0xfe, 0xff, 0xff];
+    let black = [0, 0, 0, 0xff];
+
+    let pattern = nes.pattern_table(0, 0);
+    assert_eq!(pattern.len(), viewer::PATTERN_TABLE_WIDTH * viewer::PATTERN_TABLE_HEIGHT * 4);
+    assert_eq!(pixel(&pattern, viewer::PATTERN_TABLE_WIDTH, 8, 0), white);
+    assert_eq!(pixel(&pattern, viewer::PATTERN_TABLE_WIDTH, 8, 1), black);
+
+    let nametables = nes.nametables();
+    assert_eq!(pixel(&nametables, viewer::NAMETABLES_WIDTH, 0, 0), white);
+    assert_eq!(pixel(&nametables, viewer::NAMETABLES_WIDTH, 8, 16), [0xff, 0, 0xff, 0xff]);
+    assert_eq!(pixel(&nametables, viewer::NAMETABLES_WIDTH, 9, 17), black);
+
+    let sprite = nes.sprites()[1];
+    assert_eq!((sprite.x, sprite.y, sprite.tile, sprite.palette), (0x40, 0x20, 1, 1));
+    assert!(sprite.flip_vertical && !sprite.flip_horizontal && !sprite.behind_background);
+
+    // colour 3 of palette 5 is $16, on the bottom row of the flipped tile
+    let sheet = nes.sprite_sheet();
+    assert_eq!(pixel(&sheet, viewer::SPRITE_SHEET_WIDTH, 8, 7), [0xb5, 0x31, 0x20, 0xff]);
+    assert_eq!(pixel(&sheet, viewer::SPRITE_SHEET_WIDTH, 8, 0), [0; 4]);
+
+    let palette = nes.palette_ram();
+    assert_eq!(pixel(&palette, viewer::PALETTE_WIDTH, 3, 0), white);
+    // with rendering off the screen is all backdrop
+    let screen = nes.screen();
+    assert_eq!(screen.len(), viewer::SCREEN_WIDTH * viewer::SCREEN_HEIGHT * 4);
+    assert!(screen.chunks(4).all(|rgba| rgba == black));
+
+    // sprites show up a line below their y
+    nes.write(0x2001, 0x18);
+    let screen = nes.screen();
+    assert_eq!(pixel(&screen, viewer::SCREEN_WIDTH, 0x40, 0x28), [0xb5, 0x31, 0x20, 0xff]);
+    assert_eq!(pixel(&screen, viewer::SCREEN_WIDTH, 0x40, 0x21), black);
+
+    // the background starts at the scroll
+    assert_eq!(pixel(&screen, viewer::SCREEN_WIDTH, 0, 0), black);
+    nes.read(0x2002);
+    nes.write(0x2005, 0);
+    nes.write(0x2005, 0);
+    assert_eq!(pixel(&nes.screen(), viewer::SCREEN_WIDTH, 0, 0), white);
+}
+
+#[test]
+fn png_is_stored_zlib() {
+    let rgba: Vec<u8> = (0..2 * 2 * 4).map(|i| i as u8).collect();
+    let png = png::encode(2, 2, &rgba);
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 2]);
+
+    let ihdr_crc = u32::from_be_bytes([png[29], png[30], png[31], png[32]]);
+    assert_eq!(ihdr_crc, nes::checksum::crc32(&png[12..29]));
+
+    let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+    assert_eq!(&png[37..41], b"IDAT");
+
+    let zlib = &png[41..41 + idat_len];
+    let raw: Vec<u8> = [&[0], &rgba[..8], &[0], &rgba[8..]].concat();
+
+    // header, one final stored block, then the checksum
+    assert_eq!(&zlib[..3], &[0x78, 0x01, 1]);
+    assert_eq!(&zlib[7..7 + raw.len()], &raw[..]);
+    assert_eq!(&zlib[7 + raw.len()..], &png::adler32(&raw).to_be_bytes());
+
+    assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+}
@@ -0,0 +1,99 @@
+use nes::Nes;
+use nes::movie::Movie;
+use nes::region::Region;
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+// the idle loop on a nes 2.0 header asking for `region`
+fn nes2_rom(region: u8) -> Vec<u8> {
+    let mut rom = nrom_with_program(&IDLE_LOOP);
+
+    rom[7] |= 0b00001000;
+    rom[12] = region;
+    rom
+}
+
+#[test]
+fn header_picks_the_region() {
+    let mut nes = Nes::new();
+
+    nes.load_rom(&nes2_rom(1)).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+
+    nes.load_rom(&nes2_rom(3)).unwrap();
+    assert_eq!(nes.region(), Region::Dendy);
+
+    // multi-region and ines headers leave the user's choice alone
+    nes.set_region(Region::Pal);
+    nes.load_rom(&nes2_rom(2)).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+}
+
+#[test]
+fn timing_follows_the_region() {
+    assert_eq!(Region::Ntsc.cycles_per_frame(), 29781);
+    assert_eq!(Region::Pal.cycles_per_frame(), 33248);
+    assert_eq!(Region::Dendy.cycles_per_frame(), 35464);
+
+    assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 0.001);
+    assert!((Region::Pal.frame_rate() - 50.0070).abs() < 0.001);
+
+    let mut nes = Nes::new();
+    nes.load_rom(&nes2_rom(1)).unwrap();
+
+    // pal runs 3.2 dots per cycle
+    for _ in 0..5 {
+        nes.tick_cpu();
+    }
+
+    assert_eq!(nes.bus().ppu.dot, 16);
+
+    nes.tick_frame();
+    assert_eq!((nes.bus().ppu.frame, nes.bus().ppu.scanline), (1, 241));
+
+    // two pal frames are a whole number of cycles, so they line up again
+    let start = nes.machine().master_clock();
+
+    nes.tick_frame();
+    nes.tick_frame();
+
+    assert_eq!(nes.machine().master_clock() - start, 2 * Region::Pal.master_clocks_per_frame());
+}
+
+#[test]
+fn save_states_keep_the_region() {
+    let mut nes = Nes::new();
+    nes.load_rom(&nes2_rom(3)).unwrap();
+    nes.tick_frame();
+
+    let state = nes.save_state();
+
+    nes.set_region(Region::Ntsc);
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.region(), Region::Dendy);
+
+    // and a power cycle doesn't forget it either
+    nes.power_on();
+    assert_eq!(nes.region(), Region::Dendy);
+    assert_eq!(nes.bus().ppu.region, Region::Dendy);
+}
+
+#[test]
+fn movies_carry_pal_timing() {
+    let movie = Movie::from_fm2("version 3\npalFlag 1\n|0|........|........||\n").unwrap();
+    assert_eq!(movie.region, Region::Pal);
+    assert!(movie.to_fm2().unwrap().contains("palFlag 1\n"));
+
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+    nes.play_movie(movie).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+
+    let dendy = Movie { region: Region::Dendy, ..Movie::new() };
+    assert!(dendy.to_fm2().is_err());
+}
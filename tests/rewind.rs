@@ -0,0 +1,102 @@
+use nes::Nes;
+use nes::rewind::{self, Rewind, DELTAS_PER_KEYFRAME};
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+// a state that changes a little every frame, like ram would
+fn state(frame: u64) -> Vec<u8> {
+    let mut state = vec![0x11; 600];
+
+    state[frame as usize % 600] = frame as u8;
+    state[300] = (frame >> 8) as u8;
+    state
+}
+
+#[test]
+fn codec_round_trip() {
+    let base: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+
+    let mut state = base.clone();
+    state[0] ^= 1;
+    state[2] ^= 1;
+    state[500] = 0;
+    state.extend_from_slice(&[0, 0, 0, 9]);
+
+    let cases = [
+        (&base[..], &base[..]),
+        (&base[..], &state[..]),
+        (&state[..], &base[..]),
+        (&[][..], &state[..]),
+        (&base[..], &[][..]),
+        (&[][..], &[0; 300][..]),
+    ];
+
+    for (base, state) in cases {
+        assert_eq!(rewind::decode(base, &rewind::encode(base, state)), state);
+    }
+
+    // runs longer than a one byte varint, and unchanged bytes are cheap
+    let delta = rewind::encode(&base, &state);
+    assert!(delta.len() < 20);
+}
+
+#[test]
+fn seek_finds_the_newest_snapshot_at_or_before() {
+    let mut history = Rewind::new(1, usize::MAX);
+
+    for frame in 0..DELTAS_PER_KEYFRAME as u64 * 3 {
+        history.push(frame, &state(frame));
+    }
+
+    assert_eq!(history.seek(70), Some((70, state(70))));
+    // that dropped everything after it
+    assert_eq!(history.len(), 71);
+
+    assert_eq!(history.seek(40), Some((40, state(40))));
+    assert_eq!(history.seek(31), Some((31, state(31))));
+    assert_eq!(history.seek(29), Some((29, state(29))));
+    assert_eq!(history.seek(0), Some((0, state(0))));
+}
+
+#[test]
+fn seek_past_the_start_of_history() {
+    let mut history = Rewind::new(1, usize::MAX);
+
+    for frame in 10..50 {
+        history.push(frame, &state(frame));
+    }
+
+    let (len, size) = (history.len(), history.size());
+
+    assert_eq!(history.seek(9), None);
+    assert_eq!((history.len(), history.size()), (len, size));
+    assert_eq!(history.seek(49), Some((49, state(49))));
+
+    assert_eq!(Rewind::new(1, usize::MAX).seek(0), None);
+}
+
+#[test]
+fn rewinding_further_than_history_does_nothing() {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+
+    for _ in 0..10 {
+        nes.tick_frame();
+    }
+
+    nes.enable_rewind(1, usize::MAX);
+
+    for _ in 0..5 {
+        nes.tick_frame();
+    }
+
+    let state = nes.save_state();
+
+    assert!(!nes.rewind(6));
+    assert_eq!(nes.save_state(), state);
+
+    assert!(nes.rewind(5));
+    assert!(!nes.rewind(1));
+}
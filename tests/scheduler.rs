@@ -0,0 +1,138 @@
+use nes::bus::{CpuBus, APU_STATUS, IRQ_CARTRIDGE, IRQ_FRAME_COUNTER};
+use nes::cartridge::Cartridge;
+use nes::frame_counter::FRAME_COUNTER;
+use nes::machine::Machine;
+use nes::region::Region;
+use nes::scheduler::{Event, Scheduler};
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+fn idle_machine(region: Region) -> Machine {
+    let mut machine = Machine::new();
+
+    machine.set_region(region);
+    machine.bus.insert(Cartridge::new(&nrom_with_program(&IDLE_LOOP)).unwrap());
+    machine.reset();
+    machine
+}
+
+// cpu cycles until the frame counter raises its interrupt
+fn cycles_to_frame_irq(machine: &mut Machine) -> u64 {
+    let mut cycles = 0;
+
+    while machine.bus.irq & IRQ_FRAME_COUNTER == 0 {
+        machine.clock();
+        cycles += 1;
+    }
+
+    cycles
+}
+
+#[test]
+fn events_run_in_time_order() {
+    let mut scheduler = Scheduler::new();
+
+    scheduler.schedule(30, Event::FrameCounter);
+    scheduler.schedule(10, Event::OamDma(1));
+    scheduler.schedule(10, Event::OamDma(2));
+    scheduler.schedule(20, Event::Irq { source: IRQ_CARTRIDGE, asserted: true });
+
+    assert_eq!(scheduler.next_event(), Some(10));
+    assert_eq!(scheduler.pop(10), None);
+
+    // ties keep the order they were scheduled in
+    assert_eq!(scheduler.pop(11), Some((10, Event::OamDma(1))));
+    assert_eq!(scheduler.pop(11), Some((10, Event::OamDma(2))));
+
+    scheduler.cancel(Event::FrameCounter);
+
+    let left: Vec<_> = scheduler.events().copied().collect();
+    assert_eq!(left, vec![(20, Event::Irq { source: IRQ_CARTRIDGE, asserted: true })]);
+}
+
+#[test]
+fn frame_counter_raises_irq() {
+    let mut machine = idle_machine(Region::Ntsc);
+
+    assert_eq!(cycles_to_frame_irq(&mut machine), 29830);
+    assert!(machine.cpu.irq);
+
+    // reading $4015 acknowledges it
+    assert_eq!(machine.bus.peek(APU_STATUS) & 0x40, 0x40);
+    assert_eq!(machine.bus.read(APU_STATUS) & 0x40, 0x40);
+    assert_eq!(machine.bus.read(APU_STATUS) & 0x40, 0);
+
+    machine.clock();
+    assert!(!machine.cpu.irq);
+
+    // and the sequence repeats
+    assert_eq!(cycles_to_frame_irq(&mut machine), 29830 - 1);
+
+    let mut machine = idle_machine(Region::Pal);
+    assert_eq!(cycles_to_frame_irq(&mut machine), 33254);
+}
+
+#[test]
+fn frame_counter_irq_can_be_inhibited() {
+    let mut machine = idle_machine(Region::Ntsc);
+
+    machine.bus.write(FRAME_COUNTER, 0x40);
+
+    for _ in 0..2 * 29830 {
+        machine.clock();
+    }
+
+    assert_eq!(machine.bus.irq, 0);
+
+    // five step mode never raises it either
+    machine.bus.write(FRAME_COUNTER, 0x80);
+
+    for _ in 0..2 * 37282 {
+        machine.clock();
+    }
+
+    assert_eq!(machine.bus.irq, 0);
+}
+
+#[test]
+fn scheduled_irqs_reach_the_cpu() {
+    let mut machine = idle_machine(Region::Ntsc);
+    let now = machine.master_clock();
+
+    machine.scheduler.schedule(now + 100, Event::Irq { source: IRQ_CARTRIDGE, asserted: true });
+    machine.scheduler.schedule(now + 200, Event::Irq { source: IRQ_CARTRIDGE, asserted: false });
+
+    // the cycle starting at +96 runs everything up to +108
+    for _ in 0..8 {
+        machine.clock();
+    }
+
+    assert!(!machine.cpu.irq);
+
+    machine.clock();
+    assert!(machine.cpu.irq);
+
+    for _ in 0..8 {
+        machine.clock();
+    }
+
+    assert!(!machine.cpu.irq);
+}
+
+#[test]
+fn dividers_follow_the_region() {
+    for region in [Region::Ntsc, Region::Pal, Region::Dendy].iter() {
+        let mut machine = idle_machine(*region);
+
+        for _ in 0..1000 {
+            machine.clock();
+        }
+
+        let Machine { scheduler, bus, .. } = &machine;
+
+        assert_eq!(scheduler.cpu, 1000 * region.cpu_divider());
+        assert!(bus.ppu_clock >= scheduler.cpu && bus.ppu_clock < scheduler.cpu + region.ppu_divider());
+    }
+}
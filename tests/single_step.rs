@@ -0,0 +1,190 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use nes::bus::CpuBus;
+use nes::cpu::Cpu;
+use nes::opcodes;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/single_step");
+
+// failures printed before giving up on a file
+const MAX_REPORTED: usize = 10;
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+// 64k of plain ram that records every access the cpu makes
+struct FlatBus {
+    data: Vec<u8>,
+    accesses: Vec<(u16, u8, String)>,
+}
+
+impl CpuBus for FlatBus {
+    fn read(&mut self, addr: usize) -> u8 {
+        let value = self.data[addr];
+        self.accesses.push((addr as u16, value, "read".to_string()));
+        value
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        self.data[addr] = value;
+        self.accesses.push((addr as u16, value, "write".to_string()));
+    }
+
+    fn peek(&self, addr: usize) -> u8 {
+        self.data[addr]
+    }
+}
+
+// runs a single vector, returning why it failed
+fn run(vector: &Vector, check_bus: bool) -> Result<(), String> {
+    let mut ram = FlatBus {
+        data: vec![0; 0x10000],
+        accesses: vec![],
+    };
+
+    for (addr, value) in vector.initial.ram.iter() {
+        ram.data[*addr as usize] = *value;
+    }
+
+    let mut cpu = Cpu::new();
+    let initial = &vector.initial;
+
+    cpu.pc = initial.pc;
+    cpu.sp = initial.s;
+    cpu.a = initial.a;
+    cpu.x = initial.x;
+    cpu.y = initial.y;
+    cpu.p = initial.p;
+
+    cpu.tick(&mut ram);
+
+    let cycles = cpu.skip_ticks as usize + 1;
+    let expected = &vector.expected;
+    let mut errors = vec![];
+
+    let registers = [
+        ("pc", cpu.pc, expected.pc),
+        ("s", cpu.sp as u16, expected.s as u16),
+        ("a", cpu.a as u16, expected.a as u16),
+        ("x", cpu.x as u16, expected.x as u16),
+        ("y", cpu.y as u16, expected.y as u16),
+        ("p", cpu.p as u16, expected.p as u16),
+    ];
+
+    for (name, actual, expected) in registers.iter() {
+        if actual != expected {
+            errors.push(format!("{}: {:#06x} != {:#06x}", name, actual, expected));
+        }
+    }
+
+    for (addr, value) in expected.ram.iter() {
+        let actual = ram.data[*addr as usize];
+
+        if actual != *value {
+            errors.push(format!("ram[{:#06x}]: {:#04x} != {:#04x}", addr, actual, value));
+        }
+    }
+
+    if cycles != vector.cycles.len() {
+        errors.push(format!("cycles: {} != {}", cycles, vector.cycles.len()));
+    }
+
+    if check_bus && ram.accesses != vector.cycles {
+        errors.push(format!("bus: {:?} != {:?}", ram.accesses, vector.cycles));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{}: {}", vector.name, errors.join(", ")))
+    }
+}
+
+fn run_file(path: &Path, check_bus: bool) -> Vec<String> {
+    let json = fs::read_to_string(path).unwrap();
+    let vectors: Vec<Vector> = serde_json::from_str(&json)
+        .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+    vectors.iter()
+        .filter_map(|vector| run(vector, check_bus).err())
+        .collect()
+}
+
+// the real cpu makes dummy reads and writes we don't model yet, so the
+// cycle by cycle bus comparison is opt in
+fn check_bus() -> bool {
+    env::var("SINGLE_STEP_BUS").is_ok()
+}
+
+fn assert_passes(path: &Path, failures: &[String]) {
+    assert!(
+        failures.is_empty(),
+        "{}: {} failing vectors\n{}",
+        path.display(),
+        failures.len(),
+        failures.iter().take(MAX_REPORTED).cloned().collect::<Vec<_>>().join("\n"),
+    );
+}
+
+// hand written vectors in the same format, covering the indexed modes
+// that are easiest to get wrong, like (zp),Y and $ffxx,X wrapping
+#[test]
+fn sample_vectors() {
+    let path = Path::new(FIXTURES).join("sample.json");
+    assert_passes(&path, &run_file(&path, false));
+}
+
+// the full vectors are too big to check in. fetch them with
+//
+//     git clone https://github.com/SingleStepTests/65x02
+//
+// then point SINGLE_STEP_TESTS at its `nes6502/v1` directory (one `xx.json`
+// per opcode), or copy that to tests/single_step/v1, and run
+// `cargo test --test single_step -- --ignored`
+#[test]
+#[ignore = "needs the nes6502 vectors, see the comment above"]
+fn opcode_vectors() {
+    let dir = env::var("SINGLE_STEP_TESTS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(FIXTURES).join("v1"));
+
+    assert!(dir.is_dir(), "no vectors at {}", dir.display());
+
+    let mut failed = vec![];
+
+    for opcode in 0..=0xff {
+        let path = dir.join(format!("{:02x}.json", opcode));
+
+        if opcodes::get(opcode).is_none() || !path.exists() {
+            continue;
+        }
+
+        let failures = run_file(&path, check_bus());
+
+        if !failures.is_empty() {
+            eprintln!("{:02x}: {} failing vectors, e.g. {}", opcode, failures.len(), failures[0]);
+            failed.push(opcode);
+        }
+    }
+
+    assert!(failed.is_empty(), "opcodes with failing vectors: {:02x?}", failed);
+}
@@ -0,0 +1,129 @@
+use nes::Nes;
+use nes::checksum::crc32;
+use nes::machine::{Machine, PpuSync};
+use nes::ppu::DOTS_PER_SCANLINE;
+use nes::state::{StateError, StateReader, StateWriter, HEADER_SIZE, MAGIC, VERSION};
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+fn running() -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+    nes.tick_frame();
+    nes
+}
+
+// `payload` behind a header that's valid for it
+fn wrap(payload: &[u8]) -> Vec<u8> {
+    let mut state = MAGIC.to_vec();
+
+    state.extend_from_slice(&VERSION.to_le_bytes());
+    state.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    state.extend_from_slice(&crc32(payload).to_le_bytes());
+    state.extend_from_slice(payload);
+    state
+}
+
+// a load that fails leaves the machine as it was
+fn rejects(nes: &mut Nes, state: &[u8], error: StateError) {
+    let before = nes.save_state();
+
+    assert_eq!(nes.load_state(state), Err(error));
+    assert_eq!(nes.save_state(), before);
+}
+
+#[test]
+fn round_trip() {
+    let mut nes = running();
+    let state = nes.save_state();
+    let cycles = nes.machine().cpu.cycles;
+
+    nes.tick_frame();
+    assert_ne!(nes.machine().cpu.cycles, cycles);
+
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.machine().cpu.cycles, cycles);
+    assert_eq!(nes.save_state(), state);
+}
+
+#[test]
+fn header_is_checked() {
+    let mut nes = running();
+    let state = nes.save_state();
+
+    let mut magic = state.clone();
+    magic[0] = b'X';
+    rejects(&mut nes, &magic, StateError::BadMagic);
+
+    let mut version = state.clone();
+    version[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    rejects(&mut nes, &version, StateError::IncompatibleVersion(VERSION + 1));
+
+    let mut corrupt = state.clone();
+    *corrupt.last_mut().unwrap() ^= 1;
+    rejects(&mut nes, &corrupt, StateError::BadChecksum);
+
+    rejects(&mut nes, &state[..state.len() - 1], StateError::Truncated);
+    rejects(&mut nes, &state[..HEADER_SIZE - 1], StateError::Truncated);
+}
+
+#[test]
+fn payload_must_match_the_layout() {
+    let mut nes = running();
+    let payload = nes.save_state()[HEADER_SIZE..].to_vec();
+
+    // valid headers, but a field short or a byte over
+    rejects(&mut nes, &wrap(&payload[..payload.len() - 1]), StateError::Truncated);
+
+    let mut longer = payload.clone();
+    longer.push(0);
+    rejects(&mut nes, &wrap(&longer), StateError::Invalid("trailing data"));
+}
+
+#[test]
+fn lengths_are_bounded() {
+    let mut w = StateWriter::new();
+    w.write_u32(u32::MAX);
+    w.write_u8(0);
+
+    let state = w.finish();
+    let mut r = StateReader::new(&state).unwrap();
+
+    assert_eq!(r.read_bytes(), Err(StateError::Truncated));
+
+    let mut r = StateReader::new(&state).unwrap();
+    r.read_u32().unwrap();
+    assert_eq!(r.finish(), Err(StateError::Invalid("trailing data")));
+    r.read_u8().unwrap();
+    assert_eq!(r.finish(), Ok(()));
+}
+
+// puts a value out of its range into a machine
+type Corrupt = fn(&mut Machine);
+
+#[test]
+fn positions_out_of_range_are_rejected() {
+    let bad: [(Corrupt, &str); 4] = [
+        (|machine| machine.bus.ppu.scanline = 1000, "ppu position"),
+        (|machine| machine.bus.ppu.scanline = machine.region().scanlines(), "ppu position"),
+        (|machine| machine.bus.ppu.dot = DOTS_PER_SCANLINE, "ppu position"),
+        (|machine| machine.bus.frame_counter.step = 4, "frame counter step"),
+    ];
+
+    for sync in [PpuSync::LockStep, PpuSync::CatchUp] {
+        for (corrupt, what) in bad {
+            let mut nes = running();
+            nes.set_ppu_sync(sync);
+
+            // saved with a valid checksum around the bad value
+            let mut broken = running();
+            corrupt(broken.machine_mut());
+            let state = broken.save_state();
+
+            rejects(&mut nes, &state, StateError::Invalid(what));
+            nes.tick_frame();
+        }
+    }
+}
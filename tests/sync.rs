@@ -0,0 +1,98 @@
+use nes::Nes;
+use nes::machine::PpuSync;
+use nes::region::Region;
+
+mod common;
+
+use common::nrom_with_program;
+
+// CLI; BIT $2002; BPL -5; ASL $4014; CLC; BCC -11. waits on vblank and
+// kicks off a dma each time, with the frame counter interrupt let in
+const PROGRAM: [u8; 12] = [0x58, 0x2c, 0x02, 0x20, 0x10, 0xfb, 0x0e, 0x14, 0x40, 0x18, 0x90, 0xf5];
+
+// both vectors point at ram, where this waits on vblank forever
+const HANDLER: [u8; 8] = [0x2c, 0x02, 0x20, 0x10, 0xfb, 0x18, 0x90, 0xf8];
+
+fn synced_nes(region: Region, mode: PpuSync) -> Nes {
+    let mut nes = Nes::new();
+
+    nes.load_rom(&nrom_with_program(&PROGRAM)).unwrap();
+    nes.set_region(region);
+    nes.set_ppu_sync(mode);
+
+    for (i, byte) in HANDLER.iter().enumerate() {
+        nes.write(i, *byte);
+    }
+
+    // rendering on for the odd frame skip, and nmis
+    nes.write(0x2001, 0x18);
+    nes.write(0x2000, 0x80);
+    nes
+}
+
+#[test]
+fn catch_up_matches_lock_step_frames() {
+    for region in [Region::Ntsc, Region::Pal, Region::Dendy].iter() {
+        let mut lock_step = synced_nes(*region, PpuSync::LockStep);
+        let mut catch_up = synced_nes(*region, PpuSync::CatchUp);
+
+        for frame in 0..10 {
+            lock_step.tick_frame();
+            catch_up.tick_frame();
+
+            assert!(lock_step.save_state() == catch_up.save_state(), "{:?} frame {}", region, frame);
+        }
+
+        assert_eq!(catch_up.bus().ppu.frame, 10);
+    }
+}
+
+#[test]
+fn catch_up_matches_lock_step_cycles() {
+    let mut lock_step = synced_nes(Region::Ntsc, PpuSync::LockStep);
+    let mut catch_up = synced_nes(Region::Ntsc, PpuSync::CatchUp);
+
+    // a whole state each cycle is slow, so just what the cpu could see
+    let seen = |nes: &Nes| {
+        let ppu = &nes.bus().ppu;
+        (nes.cpu().pc, nes.cpu().nmi, nes.cpu().irq, ppu.scanline, ppu.dot, ppu.status, nes.bus().ppu_clock)
+    };
+
+    for cycle in 0..100_000 {
+        assert_eq!(lock_step.tick_cpu(), catch_up.tick_cpu(), "cycle {}", cycle);
+        assert_eq!(seen(&lock_step), seen(&catch_up), "cycle {}", cycle);
+    }
+
+    assert!(lock_step.save_state() == catch_up.save_state());
+}
+
+#[test]
+fn switching_modes_keeps_the_timeline() {
+    let mut lock_step = synced_nes(Region::Ntsc, PpuSync::LockStep);
+    let mut switched = synced_nes(Region::Ntsc, PpuSync::LockStep);
+
+    for frame in 0..6 {
+        switched.set_ppu_sync(if frame % 2 == 0 { PpuSync::CatchUp } else { PpuSync::LockStep });
+
+        // stop part way into the frame so the switch lands mid frame
+        for _ in 0..1000 {
+            lock_step.tick_cpu();
+            switched.tick_cpu();
+        }
+
+        lock_step.tick_frame();
+        switched.tick_frame();
+
+        assert!(lock_step.save_state() == switched.save_state(), "frame {}", frame);
+    }
+
+    // states load into either mode
+    let state = switched.save_state();
+
+    lock_step.set_ppu_sync(PpuSync::CatchUp);
+    lock_step.load_state(&state).unwrap();
+    lock_step.tick_frame();
+    switched.tick_frame();
+
+    assert!(lock_step.save_state() == switched.save_state());
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::Path;
+use nes::Nes;
+use nes::testrom::{TestRomRunner, TestStatus, SIGNATURE};
+
+mod common;
+
+use common::{nrom_with_program, IDLE_LOOP};
+
+const ROMS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms");
+
+fn idle_nes() -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom_with_program(&IDLE_LOOP)).unwrap();
+    nes
+}
+
+fn report(nes: &mut Nes, status: u8, message: &str) {
+    nes.write(0x6000, status);
+
+    for (i, byte) in SIGNATURE.iter().chain(message.as_bytes()).chain(&[0]).enumerate() {
+        nes.write(0x6001 + i, *byte);
+    }
+}
+
+// blargg's roms aren't checked in yet, so these are ignored. copy them
+// from https://github.com/christopherpow/nes-test-roms into tests/roms/
+// and run `cargo test -- --ignored` to check them
+fn run_rom(path: &str) {
+    let rom = fs::read(Path::new(ROMS).join(path))
+        .unwrap_or_else(|error| panic!("tests/roms/{}: {}", path, error));
+
+    let mut nes = Nes::new();
+    nes.load_rom(&rom).expect("rom should load");
+
+    let result = TestRomRunner::default().run(&mut nes);
+
+    assert!(
+        result.passed(),
+        "{} finished with {:?} after {} frames:\n{}",
+        path, result.status, result.frames, result.message,
+    );
+}
+
+macro_rules! test_roms {
+    ($($name:ident => $path:expr,)*) => {
+        $(
+            #[test]
+            #[ignore = "needs the rom in tests/roms/"]
+            fn $name() {
+                run_rom($path);
+            }
+        )*
+    };
+}
+
+// mmc3_test fails to load until mapper 4 is supported
+test_roms! {
+    instr_test_v5_official_only => "instr_test-v5/official_only.nes",
+    instr_test_v5_all_instrs => "instr_test-v5/all_instrs.nes",
+    cpu_interrupts_v2 => "cpu_interrupts_v2/cpu_interrupts.nes",
+    ppu_vbl_nmi => "ppu_vbl_nmi/ppu_vbl_nmi.nes",
+    apu_test => "apu_test/apu_test.nes",
+    mmc3_test_clocking => "mmc3_test/1-clocking.nes",
+    mmc3_test_details => "mmc3_test/2-details.nes",
+    mmc3_test_a12_clocking => "mmc3_test/3-A12_clocking.nes",
+    mmc3_test_scanline_timing => "mmc3_test/4-scanline_timing.nes",
+    mmc3_test_mmc3 => "mmc3_test/5-MMC3.nes",
+    mmc3_test_mmc6 => "mmc3_test/6-MMC6.nes",
+}
+
+#[test]
+fn runner_reports_pass_with_message() {
+    let mut nes = idle_nes();
+    report(&mut nes, 0x00, "Passed");
+
+    let result = TestRomRunner::new(10).run(&mut nes);
+
+    assert_eq!(result.status, TestStatus::Passed);
+    assert_eq!(result.message, "Passed");
+    assert_eq!(result.frames, 1);
+}
+
+#[test]
+fn runner_reports_failure_code() {
+    let mut nes = idle_nes();
+    report(&mut nes, 0x03, "Failed #3");
+
+    let result = TestRomRunner::new(10).run(&mut nes);
+
+    assert_eq!(result.status, TestStatus::Failed(3));
+    assert_eq!(result.message, "Failed #3");
+}
+
+#[test]
+fn runner_waits_while_running_and_times_out() {
+    let mut nes = idle_nes();
+    report(&mut nes, 0x80, "");
+
+    let result = TestRomRunner::new(10).run(&mut nes);
+
+    assert_eq!(result.status, TestStatus::Timeout);
+    assert_eq!(result.frames, 10);
+}
+
+#[test]
+fn runner_ignores_status_without_signature() {
+    let mut nes = idle_nes();
+    nes.write(0x6000, 0x00);
+
+    let result = TestRomRunner::new(10).run(&mut nes);
+
+    assert_eq!(result.status, TestStatus::Timeout);
+    assert_eq!(result.message, "");
+}
+
+#[test]
+fn runner_reports_a_halted_cpu() {
+    let mut nes = Nes::new();
+
+    // CLC, then $02, one of the opcodes that jams a real 6502
+    nes.load_rom(&nrom_with_program(&[0x18, 0x02])).unwrap();
+
+    let result = TestRomRunner::new(10).run(&mut nes);
+
+    assert_eq!(result.status, TestStatus::Halted { pc: 0xc001, opcode: 0x02 });
+    assert_eq!(result.frames, 1);
+
+    // it stays put until a reset
+    let cycles = nes.cpu().cycles;
+    nes.tick_frame();
+    assert_eq!(nes.cpu().pc, 0xc001);
+    assert!(nes.cpu().cycles > cycles);
+
+    nes.reset();
+    assert_eq!(nes.cpu().halted, None);
+    assert_eq!(nes.cpu().pc, 0xc000);
+}